@@ -1,4 +1,222 @@
-use std::process::Command;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::port_rules::{Direction, PortRule, SocketSpec};
+
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const RULE_TIMEOUT: Duration = Duration::from_secs(10);
+// A wrong pairing port leaves `adb pair` waiting forever
+const PAIR_TIMEOUT: Duration = Duration::from_secs(15);
+const INSTALL_TIMEOUT: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeviceState {
+    Device,
+    Unauthorized,
+    Offline,
+    Authorizing,
+    Connecting,
+    Recovery,
+    Sideload,
+    Bootloader,
+    NoPermissions,
+    Other(String),
+}
+
+impl DeviceState {
+    fn parse(state: &str) -> Self {
+        match state {
+            "device" => DeviceState::Device,
+            "unauthorized" => DeviceState::Unauthorized,
+            "offline" => DeviceState::Offline,
+            "authorizing" => DeviceState::Authorizing,
+            "connecting" => DeviceState::Connecting,
+            "recovery" => DeviceState::Recovery,
+            "sideload" => DeviceState::Sideload,
+            "bootloader" => DeviceState::Bootloader,
+            // "no permissions (missing udev rules? user is in the plugdev group); see [...]"
+            "no" => DeviceState::NoPermissions,
+            other => DeviceState::Other(other.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for DeviceState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self {
+            DeviceState::Device => "device",
+            DeviceState::Unauthorized => "unauthorized",
+            DeviceState::Offline => "offline",
+            DeviceState::Authorizing => "authorizing",
+            DeviceState::Connecting => "connecting",
+            DeviceState::Recovery => "recovery",
+            DeviceState::Sideload => "sideload",
+            DeviceState::Bootloader => "bootloader",
+            DeviceState::NoPermissions => "no permissions",
+            DeviceState::Other(state) => state,
+        };
+        f.write_str(state)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Device {
+    pub id: String,
+    pub state: DeviceState,
+}
+
+impl Device {
+    pub fn is_wireless(&self) -> bool {
+        self.id.contains(':') || self.id.contains("._adb-tls-connect.")
+    }
+
+    // Only devices in the "device" state accept shell, sync and reverse commands
+    pub fn is_usable(&self) -> bool {
+        self.state == DeviceState::Device
+    }
+
+    pub fn state_hint(&self) -> Option<&'static str> {
+        match self.state {
+            DeviceState::Device => None,
+            DeviceState::Unauthorized => Some("Accept the \"Allow USB debugging?\" prompt on the device. If no prompt shows up, replug the cable or retry authorization."),
+            DeviceState::Offline => Some("The device stopped responding. Reconnect it, then re-apply any reverse rules."),
+            DeviceState::Authorizing | DeviceState::Connecting => Some("Waiting for the device to finish connecting..."),
+            DeviceState::NoPermissions => Some("This user cannot access the USB device. Check the udev rules and plugdev group."),
+            DeviceState::Recovery | DeviceState::Sideload | DeviceState::Bootloader => Some("The device is not booted into Android, most actions are unavailable."),
+            DeviceState::Other(_) => Some("The device is in an unknown state."),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InstalledPackage {
+    pub name: String,
+    pub path: String,
+    pub version_code: Option<u64>,
+    pub uid: Option<u32>,
+    pub system: bool,
+    pub disabled: bool,
+}
+
+impl InstalledPackage {
+    // "package:/data/app/~~x==/com.example-y==/base.apk=com.example versionCode:42 uid:10123"
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.trim().strip_prefix("package:")?.split_whitespace();
+        // The path may contain '=' itself, the package name never does
+        let (path, name) = fields.next()?.rsplit_once('=')?;
+        let mut package = Self {
+            name: name.to_string(),
+            path: path.to_string(),
+            version_code: None,
+            uid: None,
+            system: false,
+            disabled: false,
+        };
+        for field in fields {
+            match field.split_once(':') {
+                Some(("versionCode", value)) => package.version_code = value.parse().ok(),
+                Some(("uid", value)) => package.uid = value.parse().ok(),
+                _ => {}
+            }
+        }
+        Some(package)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DeviceDetails {
+    pub manufacturer: String,
+    pub model: String,
+    pub android_version: String,
+    pub sdk_level: String,
+    pub fingerprint: String,
+    pub abis: String,
+    pub serial: String,
+    pub screen_size: String,
+    pub screen_density: String,
+    pub battery_level: String,
+    pub battery_temperature: String,
+    pub storage_free: String,
+    pub ip_addresses: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MdnsService {
+    pub name: String,
+    pub service_type: String,
+    pub address: String,
+}
+
+impl MdnsService {
+    pub fn is_pairing(&self) -> bool {
+        self.service_type.starts_with("_adb-tls-pairing._tcp")
+    }
+
+    pub fn is_connect(&self) -> bool {
+        self.service_type.starts_with("_adb-tls-connect._tcp")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectResult {
+    Connected(String),
+    AlreadyConnected(String),
+    Refused(String),
+    TimedOut(String),
+    Unauthorized(String),
+    Failed(String),
+}
+
+impl ConnectResult {
+    fn parse(address: &str, output: &str) -> Self {
+        let message = output.trim().to_string();
+        let lower = message.to_lowercase();
+
+        if lower.starts_with("already connected") {
+            ConnectResult::AlreadyConnected(message)
+        } else if lower.starts_with("connected to") {
+            ConnectResult::Connected(message)
+        } else if lower.contains("authenticate") || lower.contains("unauthorized") {
+            ConnectResult::Unauthorized(message)
+        } else if lower.contains("refused") {
+            ConnectResult::Refused(message)
+        } else if lower.contains("timed out") || lower.contains("10060") {
+            ConnectResult::TimedOut(message)
+        } else if message.is_empty() {
+            ConnectResult::Failed(format!("No response from adb while connecting to {}", address))
+        } else {
+            ConnectResult::Failed(message)
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        matches!(self, ConnectResult::Connected(_) | ConnectResult::AlreadyConnected(_))
+    }
+}
+
+impl fmt::Display for ConnectResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectResult::Connected(m) => write!(f, "Connected: {}", m),
+            ConnectResult::AlreadyConnected(m) => write!(f, "Already connected: {}", m),
+            ConnectResult::Refused(m) => write!(f, "Connection refused (is wireless debugging enabled?): {}", m),
+            ConnectResult::TimedOut(m) => write!(f, "Connection timed out: {}", m),
+            ConnectResult::Unauthorized(m) => write!(f, "Unauthorized (accept the prompt on the device or pair first): {}", m),
+            ConnectResult::Failed(m) => write!(f, "Failed to connect: {}", m),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ShellOutput {
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
 
 pub struct AdbManager;
 
@@ -21,21 +239,580 @@ impl AdbManager {
         }
     }
 
-    pub fn get_devices(&self) -> Result<Vec<String>, String> {
+    // Runs adb with a deadline and returns (success, stdout, stderr), killing it when time runs out
+    pub fn run_adb_command_with_timeout(&self, args: &[&str], timeout: Duration) -> Result<(bool, String, String), String> {
+        let (status, stdout, stderr) = self.run_adb_command_with_status(args, timeout)?;
+        Ok((status.success(), stdout, stderr))
+    }
+
+    fn run_adb_command_with_status(&self, args: &[&str], timeout: Duration) -> Result<(ExitStatus, String, String), String> {
+        let mut child = Command::new("adb")
+            .args(args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| e.to_string())?;
+
+        // Drain the pipes on their own threads so a chatty command cannot block on a full pipe
+        let stdout = child.stdout.take().map(read_to_string_thread);
+        let stderr = child.stderr.take().map(read_to_string_thread);
+
+        let started = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait().map_err(|e| e.to_string())? {
+                break status;
+            }
+            if started.elapsed() >= timeout {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("timed out after {}s", timeout.as_secs()));
+            }
+            thread::sleep(Duration::from_millis(50));
+        };
+
+        let stdout = stdout.and_then(|h| h.join().ok()).unwrap_or_default();
+        let stderr = stderr.and_then(|h| h.join().ok()).unwrap_or_default();
+        Ok((status, stdout, stderr))
+    }
+
+    // Runs a shell command and keeps its exit code, which adb passes through on devices with shell v2
+    pub fn run_shell(&self, device: &str, command: &str, timeout: Duration) -> Result<ShellOutput, String> {
+        let (status, stdout, stderr) = self.run_adb_command_with_status(&["-s", device, "shell", command], timeout)?;
+        Ok(ShellOutput {
+            exit_code: status.code(),
+            stdout,
+            stderr,
+        })
+    }
+
+    // Starts a long-running adb process with piped stdout and stderr, e.g. logcat
+    pub fn spawn_adb_command(&self, args: &[&str]) -> Result<Child, String> {
+        Command::new("adb")
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| e.to_string())
+    }
+
+    // Like spawn_adb_command, with stdin piped too, for sessions that take input
+    pub fn spawn_adb_interactive(&self, args: &[&str]) -> Result<Child, String> {
+        Command::new("adb")
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn connect(&self, address: &str) -> ConnectResult {
+        match self.run_adb_command_with_timeout(&["connect", address], CONNECT_TIMEOUT) {
+            Ok((_, stdout, stderr)) => {
+                // adb reports most failures on stdout with a zero exit code, so look at both streams
+                ConnectResult::parse(address, &format!("{}{}", stdout, stderr))
+            }
+            Err(e) if e.starts_with("timed out") => {
+                ConnectResult::TimedOut(format!("no answer from {} ({})", address, e))
+            }
+            Err(e) => ConnectResult::Failed(e),
+        }
+    }
+
+    pub fn get_prop(&self, device: &str, name: &str) -> Result<String, String> {
+        let output = self.run_adb_command(&["-s", device, "shell", "getprop", name])?;
+        Ok(output.trim().to_string())
+    }
+
+    // Process ids of a running package; empty when it is not running (pidof exits with 1)
+    pub fn pidof(&self, device: &str, package: &str) -> Vec<u32> {
+        self.run_adb_command(&["-s", device, "shell", "pidof", package])
+            .map(|output| output.split_whitespace().filter_map(|p| p.parse().ok()).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn get_device_details(&self, device: &str) -> Result<DeviceDetails, String> {
+        // Fetch every property in one round trip; lines look like "[ro.product.model]: [Pixel 7]"
+        let props_output = self.run_adb_command(&["-s", device, "shell", "getprop"])?;
+        let props: HashMap<&str, &str> = props_output
+            .lines()
+            .filter_map(|line| {
+                let (key, value) = line.split_once("]: [")?;
+                Some((key.trim_start_matches('['), value.trim_end().trim_end_matches(']')))
+            })
+            .collect();
+        let prop = |name: &str| props.get(name).unwrap_or(&"").to_string();
+
+        let mut details = DeviceDetails {
+            manufacturer: prop("ro.product.manufacturer"),
+            model: prop("ro.product.model"),
+            android_version: prop("ro.build.version.release"),
+            sdk_level: prop("ro.build.version.sdk"),
+            fingerprint: prop("ro.build.fingerprint"),
+            abis: prop("ro.product.cpu.abilist"),
+            serial: prop("ro.serialno"),
+            ..Default::default()
+        };
+
+        // The remaining sections are best effort, older builds lack some of these commands
+        if let Ok(battery) = self.run_adb_command(&["-s", device, "shell", "dumpsys", "battery"]) {
+            for line in battery.lines() {
+                match line.trim().split_once(": ") {
+                    Some(("level", value)) => details.battery_level = format!("{}%", value),
+                    Some(("temperature", value)) => {
+                        // Reported in tenths of a degree Celsius
+                        if let Ok(tenths) = value.parse::<f32>() {
+                            details.battery_temperature = format!("{:.1} °C", tenths / 10.0);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        // An override set with `wm size` takes precedence over the physical value
+        let last_value = |output: String| {
+            output.lines().filter_map(|l| l.split_once(": ")).map(|(_, v)| v.trim().to_string()).next_back()
+        };
+        if let Some(size) = self.run_adb_command(&["-s", device, "shell", "wm", "size"]).ok().and_then(last_value) {
+            details.screen_size = size;
+        }
+        if let Some(density) = self.run_adb_command(&["-s", device, "shell", "wm", "density"]).ok().and_then(last_value) {
+            details.screen_density = format!("{} dpi", density);
+        }
+
+        if let Ok(df) = self.run_adb_command(&["-s", device, "shell", "df", "-h", "/data"]) {
+            // Filesystem Size Used Avail Use% Mounted on
+            if let Some(line) = df.lines().skip(1).last() {
+                let columns: Vec<&str> = line.split_whitespace().collect();
+                if columns.len() >= 4 {
+                    details.storage_free = format!("{} free of {}", columns[3], columns[1]);
+                }
+            }
+        }
+
+        if let Ok(addresses) = self.run_adb_command(&["-s", device, "shell", "ip", "-o", "addr"]) {
+            // "30: wlan0    inet 192.168.1.105/24 brd 192.168.1.255 scope global wlan0"
+            for line in addresses.lines() {
+                let columns: Vec<&str> = line.split_whitespace().collect();
+                if columns.len() >= 4 && columns[1] != "lo" && columns[2].starts_with("inet") {
+                    details.ip_addresses.push(format!("{} {}", columns[1], columns[3]));
+                }
+            }
+        }
+
+        Ok(details)
+    }
+
+    pub fn get_wlan_ip(&self, device: &str) -> Result<String, String> {
+        // "192.168.1.0/24 dev wlan0 proto kernel scope link src 192.168.1.105"
+        let routes = self.run_adb_command(&["-s", device, "shell", "ip", "route"])?;
+        for line in routes.lines().filter(|l| l.contains("wlan")) {
+            let mut parts = line.split_whitespace();
+            if parts.by_ref().any(|p| p == "src") {
+                if let Some(ip) = parts.next() {
+                    return Ok(ip.to_string());
+                }
+            }
+        }
+
+        // Some devices keep the wlan route in a separate table, so fall back to the interface itself
+        let addr = self.run_adb_command(&["-s", device, "shell", "ip", "-f", "inet", "addr", "show", "wlan0"])?;
+        for line in addr.lines() {
+            let mut parts = line.split_whitespace();
+            if parts.next() == Some("inet") {
+                if let Some(ip) = parts.next().and_then(|cidr| cidr.split('/').next()) {
+                    return Ok(ip.to_string());
+                }
+            }
+        }
+
+        Err("Could not find a Wi-Fi IP address (is the device on Wi-Fi?)".to_string())
+    }
+
+    // Returns adb's trimmed stdout, which holds the assigned port for a host tcp:0 forward
+    pub fn apply_rule(&self, device: &str, rule: &PortRule) -> Result<String, String> {
+        let mut args = vec!["-s".to_string(), device.to_string()];
+        args.extend(rule.args());
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let (success, stdout, stderr) = self.run_adb_command_with_timeout(&args, RULE_TIMEOUT)?;
+
+        // Older adb versions print "error: ..." for a rejected rule but still exit with 0
+        if success && !stderr.contains("error:") {
+            return Ok(stdout.trim().to_string());
+        }
+        let message = [stderr.trim(), stdout.trim()]
+            .into_iter()
+            .find(|s| !s.is_empty())
+            .unwrap_or("adb exited with an error but printed nothing");
+        Err(message.to_string())
+    }
+
+    // Checks the device's mapping list for the rule, since a zero exit code alone proves little
+    pub fn verify_rule(&self, device: &str, rule: &PortRule) -> Result<bool, String> {
+        let active = match rule.direction {
+            Direction::Reverse => self.list_reverse(device)?,
+            Direction::Forward => self.list_forward(device)?,
+        };
+        Ok(active.iter().any(|r| r.device == rule.device && r.host == rule.host))
+    }
+
+    pub fn remove_rule(&self, device: &str, rule: &PortRule) -> Result<String, String> {
+        match rule.direction {
+            Direction::Reverse => self.reverse_remove(device, &rule.device),
+            Direction::Forward => self.forward_remove(device, &rule.host),
+        }
+    }
+
+    pub fn reverse_remove(&self, device: &str, device_spec: &SocketSpec) -> Result<String, String> {
+        self.run_adb_command(&["-s", device, "reverse", "--remove", &device_spec.to_string()])
+    }
+
+    pub fn reverse_remove_all(&self, device: &str) -> Result<String, String> {
+        self.run_adb_command(&["-s", device, "reverse", "--remove-all"])
+    }
+
+    pub fn forward_remove_all(&self, device: &str) -> Result<String, String> {
+        self.run_adb_command(&["-s", device, "forward", "--remove-all"])
+    }
+
+    pub fn list_reverse(&self, device: &str) -> Result<Vec<PortRule>, String> {
+        // "<serial> <device socket> <host socket>"
+        let output = self.run_adb_command(&["-s", device, "reverse", "--list"])?;
+        Ok(parse_mapping_list(&output, Direction::Reverse))
+    }
+
+    pub fn list_forward(&self, device: &str) -> Result<Vec<PortRule>, String> {
+        // "<serial> <host socket> <device socket>", the reverse order of `reverse --list`.
+        // The host keeps forwards for every device in one list, so keep only this device's lines
+        let output = self.run_adb_command(&["-s", device, "forward", "--list"])?;
+        let output: String = output
+            .lines()
+            .filter(|line| line.split_whitespace().next() == Some(device))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        Ok(parse_mapping_list(&output, Direction::Forward))
+    }
+
+    pub fn forward_remove(&self, device: &str, host_spec: &SocketSpec) -> Result<String, String> {
+        self.run_adb_command(&["-s", device, "forward", "--remove", &host_spec.to_string()])
+    }
+
+    // Every package for user 0, tagged as system and disabled from pm's filtered lists
+    pub fn list_packages(&self, device: &str) -> Result<Vec<InstalledPackage>, String> {
+        let output = self.run_adb_command(&["-s", device, "shell", "pm", "list", "packages", "-f", "-U", "--show-versioncode"])?;
+        let names = |filter: &str| -> Result<HashSet<String>, String> {
+            let output = self.run_adb_command(&["-s", device, "shell", "pm", "list", "packages", filter])?;
+            Ok(output
+                .lines()
+                .filter_map(|line| line.trim().strip_prefix("package:"))
+                .map(str::to_string)
+                .collect())
+        };
+        let system = names("-s")?;
+        let disabled = names("-d")?;
+
+        let mut packages: Vec<InstalledPackage> = output
+            .lines()
+            .filter_map(InstalledPackage::parse)
+            .map(|mut package| {
+                package.system = system.contains(&package.name);
+                package.disabled = disabled.contains(&package.name);
+                package
+            })
+            .collect();
+        if packages.is_empty() && !output.trim().is_empty() {
+            return Err(output.trim().to_string());
+        }
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(packages)
+    }
+
+    // `flags` are install options like -r; several APKs are installed as the splits of one app
+    pub fn install(&self, device: &str, apks: &[PathBuf], flags: &[&str]) -> Result<String, String> {
+        let subcommand = if apks.len() > 1 { "install-multiple" } else { "install" };
+        let mut args = vec!["-s", device, subcommand];
+        args.extend_from_slice(flags);
+        let paths: Vec<String> = apks.iter().map(|p| p.to_string_lossy().into_owned()).collect();
+        args.extend(paths.iter().map(String::as_str));
+
+        // pm reports "Failure [INSTALL_...]" on stdout or stderr depending on the adb version
+        let (success, stdout, stderr) = self.run_adb_command_with_timeout(&args, INSTALL_TIMEOUT)?;
+        let output = format!("{}{}", stdout, stderr).trim().to_string();
+        if success && !output.contains("Failure") {
+            Ok(output)
+        } else if output.is_empty() {
+            Err("adb install failed".to_string())
+        } else {
+            Err(output)
+        }
+    }
+
+    pub fn push(&self, device: &str, local: &Path, remote: &str) -> Result<String, String> {
+        self.run_adb_command(&["-s", device, "push", &local.to_string_lossy(), remote])
+    }
+
+    pub fn tcpip(&self, device: &str, port: u16) -> Result<String, String> {
+        self.run_adb_command(&["-s", device, "tcpip", &port.to_string()])
+    }
+
+    pub fn list_devices(&self) -> Result<Vec<Device>, String> {
         let output = self.run_adb_command(&["devices"])?;
         let mut devices = Vec::new();
-        
+
         // Skip the first line which is "List of devices attached"
         for line in output.lines().skip(1) {
-            if line.trim().is_empty() {
-                continue;
-            }
-            
-            if let Some(device_id) = line.split_whitespace().next() {
-                devices.push(device_id.to_string());
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() >= 2 {
+                devices.push(Device {
+                    id: parts[0].to_string(),
+                    state: DeviceState::parse(parts[1]),
+                });
             }
         }
-        
+
         Ok(devices)
     }
-} 
\ No newline at end of file
+
+    // Restarting the server makes adb re-send its key, which brings the RSA prompt back
+    pub fn restart_server(&self) -> Result<String, String> {
+        self.run_adb_command(&["kill-server"])?;
+        self.run_adb_command(&["start-server"])
+    }
+
+    pub fn reconnect_offline(&self, device: &Device) -> Result<String, String> {
+        if device.is_wireless() {
+            let _ = self.run_adb_command(&["disconnect", &device.id]);
+            let result = self.connect(&device.id);
+            if result.is_connected() {
+                Ok(result.to_string())
+            } else {
+                Err(result.to_string())
+            }
+        } else {
+            self.run_adb_command(&["reconnect", "offline"])
+        }
+    }
+
+    pub fn pair(&self, address: &str, code: &str) -> Result<String, String> {
+        let (_, stdout, stderr) = self.run_adb_command_with_timeout(&["pair", address, code], PAIR_TIMEOUT)?;
+        let output = format!("{}{}", stdout, stderr);
+
+        // adb exits successfully even when pairing is rejected, so check the message
+        if output.contains("Successfully paired") {
+            Ok(output.trim().to_string())
+        } else {
+            Err(output.trim().to_string())
+        }
+    }
+
+    pub fn get_mdns_services(&self) -> Result<Vec<MdnsService>, String> {
+        let output = self.run_adb_command(&["mdns", "services"])?;
+        Ok(parse_mdns_services(&output))
+    }
+}
+
+// Parses `adb mdns services`: "<instance name> <service type> <address:port>" per service, after a
+// "List of discovered mdns services" header and possibly "* daemon started" lines
+pub fn parse_mdns_services(output: &str) -> Vec<MdnsService> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let [name @ .., service_type, address] = parts.as_slice() else {
+                return None;
+            };
+            if name.is_empty() || !service_type.starts_with('_') || !service_type.contains("._tcp") {
+                return None;
+            }
+            let (host, port) = address.rsplit_once(':')?;
+            port.parse::<u16>().ok()?;
+            // Bracket bare IPv6 hosts so the address can be passed to `adb connect` as is
+            let address = if host.contains(':') && !host.starts_with('[') {
+                format!("[{}]:{}", host, port)
+            } else {
+                address.to_string()
+            };
+            Some(MdnsService {
+                name: name.join(" "),
+                service_type: service_type.to_string(),
+                address,
+            })
+        })
+        .collect()
+}
+
+fn read_to_string_thread<R: Read + Send + 'static>(mut reader: R) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        let _ = reader.read_to_end(&mut buffer);
+        String::from_utf8_lossy(&buffer).into_owned()
+    })
+}
+
+fn parse_mapping_list(output: &str, direction: Direction) -> Vec<PortRule> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() < 3 {
+                return None;
+            }
+            let (device, host) = match direction {
+                Direction::Reverse => (parts[1], parts[2]),
+                Direction::Forward => (parts[2], parts[1]),
+            };
+            Some(PortRule {
+                direction,
+                device: SocketSpec::parse(device).ok()?,
+                host: SocketSpec::parse(host).ok()?,
+                no_rebind: false,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_connect_and_pairing_services() {
+        let output = "List of discovered mdns services\n\
+            adb-R58M123ABC-Xc9kLp\t_adb-tls-connect._tcp.\t192.168.1.42:37123\n\
+            studio-k3j9x0a1b2\t_adb-tls-pairing._tcp.\t192.168.1.42:41235\n\
+            adb-emulator-5554\t_adb._tcp.\t10.0.2.15:5555\n";
+        let services = parse_mdns_services(output);
+
+        assert_eq!(services.len(), 3);
+        assert_eq!(services[0].name, "adb-R58M123ABC-Xc9kLp");
+        assert_eq!(services[0].address, "192.168.1.42:37123");
+        assert!(services[0].is_connect() && !services[0].is_pairing());
+        assert_eq!(services[1].name, "studio-k3j9x0a1b2");
+        assert!(services[1].is_pairing() && !services[1].is_connect());
+        assert!(!services[2].is_connect() && !services[2].is_pairing());
+    }
+
+    #[test]
+    fn brackets_ipv6_service_addresses() {
+        let output = "List of discovered mdns services\n\
+            adb-R58M123ABC-Xc9kLp _adb-tls-connect._tcp fe80::1c2b:3aff:fe4d:5e6f:37123\n\
+            adb-R58M123ABC-Xc9kLp _adb-tls-pairing._tcp [fd00::42]:41235\n";
+        let services = parse_mdns_services(output);
+
+        assert_eq!(services.len(), 2);
+        assert_eq!(services[0].address, "[fe80::1c2b:3aff:fe4d:5e6f]:37123");
+        assert_eq!(services[1].address, "[fd00::42]:41235");
+    }
+
+    #[test]
+    fn skips_headers_and_junk_lines() {
+        let output = "* daemon not running; starting now at tcp:5037\n\
+            * daemon started successfully\n\
+            List of discovered mdns services\n\
+            \n\
+            ERROR: mdns discovery is disabled\n\
+            adb-R58M123ABC-Xc9kLp _adb-tls-connect._tcp. 192.168.1.42\n\
+            adb-R58M123ABC-Xc9kLp _adb-tls-connect._tcp. 192.168.1.42:port\n\
+            adb-R58M123ABC-Xc9kLp _adb-tls-connect._tcp. 192.168.1.42:37123\n";
+        let services = parse_mdns_services(output);
+
+        assert_eq!(services.len(), 1);
+        assert_eq!(services[0].address, "192.168.1.42:37123");
+    }
+
+    #[test]
+    fn classifies_connect_output() {
+        let parse = |output| ConnectResult::parse("192.168.1.42:5555", output);
+
+        assert_eq!(parse("connected to 192.168.1.42:5555\n"), ConnectResult::Connected("connected to 192.168.1.42:5555".to_string()));
+        assert!(matches!(parse("already connected to 192.168.1.42:5555"), ConnectResult::AlreadyConnected(_)));
+        assert!(matches!(
+            parse("failed to connect to '192.168.1.42:5555': Connection refused"),
+            ConnectResult::Refused(_)
+        ));
+        assert!(matches!(
+            parse("failed to authenticate to 192.168.1.42:5555"),
+            ConnectResult::Unauthorized(_)
+        ));
+        assert!(matches!(parse("192.168.1.42:5555: device unauthorized."), ConnectResult::Unauthorized(_)));
+        assert!(matches!(
+            parse("failed to connect to '192.168.1.42:5555': Connection timed out"),
+            ConnectResult::TimedOut(_)
+        ));
+        assert!(matches!(
+            parse("cannot connect to 192.168.1.42:5555: A connection attempt failed (10060)"),
+            ConnectResult::TimedOut(_)
+        ));
+        assert!(matches!(parse("failed to resolve host: 'nope'"), ConnectResult::Failed(_)));
+    }
+
+    #[test]
+    fn empty_connect_output_names_the_address() {
+        let result = ConnectResult::parse("192.168.1.42:5555", "  \n");
+        assert_eq!(
+            result,
+            ConnectResult::Failed("No response from adb while connecting to 192.168.1.42:5555".to_string())
+        );
+        assert!(!result.is_connected());
+        assert!(ConnectResult::AlreadyConnected(String::new()).is_connected());
+    }
+
+    #[test]
+    fn parses_package_lines() {
+        let package = InstalledPackage::parse(
+            "package:/data/app/~~Xy1_Zw==/com.example.app-AbC9==/base.apk=com.example.app versionCode:42 uid:10123",
+        )
+        .unwrap();
+        assert_eq!(package.name, "com.example.app");
+        assert_eq!(package.path, "/data/app/~~Xy1_Zw==/com.example.app-AbC9==/base.apk");
+        assert_eq!(package.version_code, Some(42));
+        assert_eq!(package.uid, Some(10123));
+    }
+
+    #[test]
+    fn version_code_and_uid_are_optional() {
+        let package = InstalledPackage::parse("package:/system/app/Settings/Settings.apk=com.android.settings").unwrap();
+        assert_eq!(package.name, "com.android.settings");
+        assert_eq!(package.version_code, None);
+        assert_eq!(package.uid, None);
+
+        let package = InstalledPackage::parse("package:/data/app/base.apk=com.example versionCode:abc uid:").unwrap();
+        assert_eq!(package.version_code, None);
+        assert_eq!(package.uid, None);
+    }
+
+    #[test]
+    fn skips_junk_package_lines() {
+        assert!(InstalledPackage::parse("").is_none());
+        assert!(InstalledPackage::parse("Error: java.lang.SecurityException").is_none());
+        assert!(InstalledPackage::parse("package:").is_none());
+        assert!(InstalledPackage::parse("package:/data/app/base.apk").is_none());
+    }
+
+    #[test]
+    fn parses_reverse_list() {
+        let output = "UsbFfs tcp:8081 tcp:8081\n\
+            UsbFfs localabstract:devtools tcp:9229\n\
+            garbage\n";
+        let rules = parse_mapping_list(output, Direction::Reverse);
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0], PortRule::tcp(Direction::Reverse, 8081, 8081));
+        assert_eq!(rules[1].device.to_string(), "localabstract:devtools");
+        assert_eq!(rules[1].host.to_string(), "tcp:9229");
+    }
+
+    #[test]
+    fn parses_forward_list() {
+        let output = "emulator-5554 tcp:9222 localabstract:chrome_devtools_remote\n\
+            192.168.1.42:5555 tcp:41237 tcp:8000\n\
+            emulator-5554 tcp:1 bogus\n";
+        let rules = parse_mapping_list(output, Direction::Forward);
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].host.to_string(), "tcp:9222");
+        assert_eq!(rules[0].device.to_string(), "localabstract:chrome_devtools_remote");
+        assert_eq!(rules[1], PortRule::tcp(Direction::Forward, 8000, 41237));
+        assert!(parse_mapping_list("", Direction::Forward).is_empty());
+    }
+}
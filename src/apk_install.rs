@@ -0,0 +1,273 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// One `adb install` or `adb install-multiple` call
+pub struct InstallUnit {
+    pub name: String,
+    pub apks: Vec<PathBuf>,
+    // OBB expansion files from an .xapk: local path and destination on the device
+    pub obbs: Vec<(PathBuf, String)>,
+}
+
+pub fn is_bundle(path: &Path) -> bool {
+    matches!(extension(path).as_str(), "apks" | "xapk")
+}
+
+pub fn is_installable(path: &Path) -> bool {
+    extension(path) == "apk" || is_bundle(path)
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+// Unpacks .apks (bundletool) and .xapk bundles into `work_dir` so their APKs can be installed together.
+// `index` keeps bundles with the same file name from different folders apart.
+pub fn extract_bundle(bundle: &Path, index: usize, work_dir: &Path) -> Result<InstallUnit, String> {
+    let file = File::open(bundle).map_err(|e| format!("{}: {}", bundle.display(), e))?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| format!("{}: {}", bundle.display(), e))?;
+    let target = work_dir.join(format!("{}_{}", index, file_name(bundle)));
+    fs::create_dir_all(&target).map_err(|e| e.to_string())?;
+
+    let names: Vec<String> = archive.file_names().map(str::to_string).collect();
+    // bundletool archives carry both splits/ and a standalone/ fallback; the splits are what install-multiple wants
+    let has_splits = names.iter().any(|n| n.starts_with("splits/") && n.ends_with(".apk"));
+
+    let mut unit = InstallUnit {
+        name: file_name(bundle),
+        apks: Vec::new(),
+        obbs: Vec::new(),
+    };
+    for (index, name) in names.iter().enumerate() {
+        let is_apk = name.ends_with(".apk") && (!has_splits || name.starts_with("splits/"));
+        let is_obb = name.ends_with(".obb") && name.starts_with("Android/obb/");
+        if !is_apk && !is_obb {
+            continue;
+        }
+        if is_obb && name.split('/').any(|part| part == "..") {
+            return Err(format!("{}: refusing OBB path {}", bundle.display(), name));
+        }
+
+        // Flatten the archive path; entries are numbered so names from different folders can't collide
+        let local = target.join(format!("{}_{}", index, name.rsplit('/').next().unwrap_or(name)));
+        let mut entry = archive.by_index(index).map_err(|e| e.to_string())?;
+        let mut out = File::create(&local).map_err(|e| e.to_string())?;
+        io::copy(&mut entry, &mut out).map_err(|e| format!("extracting {}: {}", name, e))?;
+
+        if is_apk {
+            unit.apks.push(local);
+        } else {
+            unit.obbs.push((local, format!("/sdcard/{}", name)));
+        }
+    }
+
+    if unit.apks.is_empty() {
+        return Err(format!("{} contains no APK files", bundle.display()));
+    }
+    Ok(unit)
+}
+
+// Groups the picked files into install calls: each bundle on its own, loose APKs either as
+// the splits of one app or one call each
+pub fn plan_install(files: &[PathBuf], as_splits: bool, work_dir: &Path) -> Result<Vec<InstallUnit>, String> {
+    let mut units = Vec::new();
+    let loose: Vec<PathBuf> = files.iter().filter(|f| !is_bundle(f)).cloned().collect();
+    if as_splits && !loose.is_empty() {
+        units.push(InstallUnit {
+            name: format!("{} split APK(s)", loose.len()),
+            apks: loose,
+            obbs: Vec::new(),
+        });
+    } else {
+        units.extend(loose.into_iter().map(|apk| InstallUnit {
+            name: file_name(&apk),
+            apks: vec![apk],
+            obbs: Vec::new(),
+        }));
+    }
+    for (index, bundle) in files.iter().filter(|f| is_bundle(f)).enumerate() {
+        units.push(extract_bundle(bundle, index, work_dir)?);
+    }
+    Ok(units)
+}
+
+pub fn work_dir() -> PathBuf {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    std::env::temp_dir().join(format!("adb_manager_install_{}", nanos))
+}
+
+// The INSTALL_... code in adb's output with a plain explanation, when it is a known one
+pub fn explain_failure(output: &str) -> Option<(String, &'static str)> {
+    let start = output.find("INSTALL_")?;
+    let code: String = output[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || *c == '_')
+        .collect();
+    let explanation = match code.as_str() {
+        "INSTALL_FAILED_ALREADY_EXISTS" => "The app is already installed. Enable \"Replace existing\" (-r).",
+        "INSTALL_FAILED_VERSION_DOWNGRADE" => "The installed version is newer. Enable \"Allow downgrade\" (-d) or uninstall first.",
+        "INSTALL_FAILED_UPDATE_INCOMPATIBLE" => "The installed app is signed with a different key. Uninstall it first (this deletes its data).",
+        "INSTALL_PARSE_FAILED_INCONSISTENT_CERTIFICATES" => "The APKs are signed with different keys, or differ from the installed app's key.",
+        "INSTALL_PARSE_FAILED_NO_CERTIFICATES" => "The APK is not signed, or its signature is broken.",
+        "INSTALL_FAILED_TEST_ONLY" => "The APK is marked test-only. Enable \"Allow test packages\" (-t).",
+        "INSTALL_FAILED_OLDER_SDK" => "The app needs a newer Android version than the device has (minSdkVersion).",
+        "INSTALL_FAILED_NEWER_SDK" => "The app was built for an older Android version than this device supports.",
+        "INSTALL_FAILED_DEPRECATED_SDK_VERSION" => "The app targets an Android version too old for this device to accept (targetSdkVersion).",
+        "INSTALL_FAILED_NO_MATCHING_ABIS" | "INSTALL_FAILED_CPU_ABI_INCOMPATIBLE" => {
+            "The APK has native code for a different CPU (e.g. arm64 only on an x86 emulator)."
+        }
+        "INSTALL_FAILED_INSUFFICIENT_STORAGE" => "The device is out of storage space.",
+        "INSTALL_FAILED_INVALID_APK" | "INSTALL_PARSE_FAILED_NOT_APK" => "The file is not a valid APK or is corrupted.",
+        "INSTALL_PARSE_FAILED_MANIFEST_MALFORMED" => "The APK's AndroidManifest.xml is invalid.",
+        "INSTALL_FAILED_MISSING_SPLIT" => "A required split APK is missing. Install all splits together.",
+        "INSTALL_FAILED_MISSING_SHARED_LIBRARY" => "The app needs a shared library that the device does not have.",
+        "INSTALL_FAILED_DUPLICATE_PACKAGE" | "INSTALL_FAILED_CONFLICTING_PROVIDER" => {
+            "Another installed app already uses this package or content provider name."
+        }
+        "INSTALL_FAILED_SHARED_USER_INCOMPATIBLE" => "The app's sharedUserId conflicts with an installed app.",
+        "INSTALL_FAILED_USER_RESTRICTED" => {
+            "Installing over USB was blocked. Allow it on the device (some vendors have an \"Install via USB\" setting)."
+        }
+        "INSTALL_FAILED_VERIFICATION_FAILURE" => "Package verification (e.g. Play Protect) rejected the app.",
+        "INSTALL_FAILED_ABORTED" => "The installation was cancelled on the device.",
+        "INSTALL_FAILED_INVALID_INSTALL_LOCATION" | "INSTALL_FAILED_MEDIA_UNAVAILABLE" => "The install location is not available.",
+        "INSTALL_FAILED_PERMISSION_MODEL_DOWNGRADE" => "The new version targets an older permission model than the installed one. Uninstall first.",
+        "INSTALL_FAILED_INTERNAL_ERROR" => "The package manager hit an internal error. Check logcat for details.",
+        _ => return None,
+    };
+    Some((code, explanation))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use zip::write::SimpleFileOptions;
+
+    // A fresh directory under the system temp dir holding a zip with the given entries; bundles
+    // are unpacked into its "work" folder
+    fn write_bundle(file_name: &str, entries: &[&str]) -> (PathBuf, PathBuf) {
+        static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "adb_manager_test_{}_{}",
+            std::process::id(),
+            NEXT_DIR.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(file_name);
+        let mut zip = zip::ZipWriter::new(File::create(&path).unwrap());
+        for entry in entries {
+            zip.start_file(*entry, SimpleFileOptions::default()).unwrap();
+            zip.write_all(entry.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        (dir, path)
+    }
+
+    fn apk_names(unit: &InstallUnit) -> Vec<String> {
+        unit.apks.iter().map(|apk| fs::read_to_string(apk).unwrap()).collect()
+    }
+
+    #[test]
+    fn explains_known_failures() {
+        let (code, explanation) =
+            explain_failure("Failure [INSTALL_FAILED_VERSION_DOWNGRADE: Downgrade detected: Update version code 1 is older than current 2]")
+                .unwrap();
+        assert_eq!(code, "INSTALL_FAILED_VERSION_DOWNGRADE");
+        assert!(explanation.contains("downgrade"));
+
+        let (code, _) = explain_failure("adb: failed to install app.apk: Failure [INSTALL_PARSE_FAILED_NO_CERTIFICATES: Failed to collect certificates]").unwrap();
+        assert_eq!(code, "INSTALL_PARSE_FAILED_NO_CERTIFICATES");
+    }
+
+    #[test]
+    fn unknown_failures_have_no_explanation() {
+        assert!(explain_failure("Failure [INSTALL_FAILED_SOMETHING_NEW: whatever]").is_none());
+        assert!(explain_failure("error: device offline").is_none());
+    }
+
+    #[test]
+    fn bundletool_archives_install_their_splits() {
+        let (dir, path) = write_bundle(
+            "app.apks",
+            &["toc.pb", "splits/base-master.apk", "splits/base-arm64_v8a.apk", "standalones/standalone-arm64_v8a.apk"],
+        );
+        let unit = extract_bundle(&path, 0, &dir.join("work")).unwrap();
+        assert_eq!(unit.name, "app.apks");
+        assert_eq!(apk_names(&unit), ["splits/base-master.apk", "splits/base-arm64_v8a.apk"]);
+        assert!(unit.obbs.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn xapk_obbs_go_to_shared_storage() {
+        let (dir, path) = write_bundle(
+            "game.xapk",
+            &["manifest.json", "com.example.game.apk", "config.arm64_v8a.apk", "Android/obb/com.example.game/main.1.com.example.game.obb"],
+        );
+        let unit = extract_bundle(&path, 0, &dir.join("work")).unwrap();
+        assert_eq!(apk_names(&unit), ["com.example.game.apk", "config.arm64_v8a.apk"]);
+        let remotes: Vec<&str> = unit.obbs.iter().map(|(_, remote)| remote.as_str()).collect();
+        assert_eq!(remotes, ["/sdcard/Android/obb/com.example.game/main.1.com.example.game.obb"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_obb_paths_that_leave_the_obb_folder() {
+        let (dir, path) = write_bundle("evil.xapk", &["base.apk", "Android/obb/../../data/evil.obb"]);
+        assert!(extract_bundle(&path, 0, &dir.join("work")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn bundles_without_apks_are_an_error() {
+        let (dir, path) = write_bundle("empty.xapk", &["manifest.json"]);
+        assert!(extract_bundle(&path, 0, &dir.join("work")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn plans_loose_apks_and_bundles() {
+        let (dir, bundle) = write_bundle("app.xapk", &["base.apk"]);
+        let files = vec![PathBuf::from("a.apk"), bundle, PathBuf::from("b.APK")];
+
+        let units = plan_install(&files, false, &dir.join("work")).unwrap();
+        let names: Vec<&str> = units.iter().map(|u| u.name.as_str()).collect();
+        assert_eq!(names, ["a.apk", "b.APK", "app.xapk"]);
+
+        let units = plan_install(&files, true, &dir.join("work")).unwrap();
+        assert_eq!(units.len(), 2);
+        assert_eq!(units[0].name, "2 split APK(s)");
+        assert_eq!(units[0].apks, [PathBuf::from("a.apk"), PathBuf::from("b.APK")]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn bundles_with_the_same_name_do_not_overwrite_each_other() {
+        // Same file name, different folders
+        let (first_dir, first) = write_bundle("app.xapk", &["base.apk"]);
+        let (second_dir, second) = write_bundle("app.xapk", &["base.apk", "config.xxhdpi.apk"]);
+
+        let units = plan_install(&[first, second], false, &first_dir.join("work")).unwrap();
+        assert_ne!(units[0].apks[0], units[1].apks[0]);
+        assert_eq!(fs::read_to_string(&units[0].apks[0]).unwrap(), "base.apk");
+        assert_eq!(apk_names(&units[1]), ["base.apk", "config.xxhdpi.apk"]);
+        assert_eq!(units[0].apks.len(), 1);
+        fs::remove_dir_all(&first_dir).unwrap();
+        fs::remove_dir_all(&second_dir).unwrap();
+    }
+}
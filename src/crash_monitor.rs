@@ -0,0 +1,447 @@
+use eframe::egui;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::adb::{AdbManager, Device};
+use crate::logcat::{LogBuffer, LogEntry, LogcatStream};
+use crate::watchdog::utc_timestamp;
+
+const MAX_REPORTS: usize = 100;
+// A crash is complete once its process has logged nothing more for this long
+const TRACE_IDLE: Duration = Duration::from_secs(1);
+const ANR_DIR_INTERVAL: Duration = Duration::from_secs(15);
+// Don't respawn a watcher whose logcat died more often than this
+const RESTART_INTERVAL: Duration = Duration::from_secs(10);
+// An ANR trace file this soon after an am_anr event is attached to that report
+const ANR_MERGE_WINDOW: Duration = Duration::from_secs(60);
+const MAX_ANR_TRACE_LINES: usize = 400;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CrashKind {
+    JavaCrash,
+    NativeCrash,
+    Anr,
+}
+
+impl fmt::Display for CrashKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CrashKind::JavaCrash => write!(f, "FATAL EXCEPTION"),
+            CrashKind::NativeCrash => write!(f, "Native crash"),
+            CrashKind::Anr => write!(f, "ANR"),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CrashReport {
+    pub id: u64,
+    pub time: String,
+    pub device_id: String,
+    pub kind: CrashKind,
+    // Empty when the package could not be determined
+    pub package: String,
+    pub summary: String,
+    pub trace: String,
+    pub seen: bool,
+    received: Instant,
+}
+
+#[derive(Default)]
+struct MonitorState {
+    reports: VecDeque<CrashReport>,
+    // Reports for other packages are dropped; empty watches everything
+    watched_packages: Vec<String>,
+    next_id: u64,
+}
+
+impl MonitorState {
+    fn is_watched(&self, package: &str) -> bool {
+        self.watched_packages.is_empty() || self.watched_packages.iter().any(|p| p == package)
+    }
+
+    fn report(&mut self, device_id: &str, kind: CrashKind, package: String, summary: String, trace: String) {
+        if !self.is_watched(&package) {
+            return;
+        }
+        if self.reports.len() >= MAX_REPORTS {
+            self.reports.pop_front();
+        }
+        self.next_id += 1;
+        self.reports.push_back(CrashReport {
+            id: self.next_id,
+            time: utc_timestamp(),
+            device_id: device_id.to_string(),
+            kind,
+            package,
+            summary,
+            trace,
+            seen: false,
+            received: Instant::now(),
+        });
+    }
+
+    // The am_anr event arrives first; the trace file written a little later belongs to it
+    fn attach_anr_trace(&mut self, device_id: &str, package: &str, file: &str, trace: &str) -> bool {
+        let recent = self.reports.iter_mut().rev().find(|r| {
+            r.device_id == device_id
+                && r.kind == CrashKind::Anr
+                && r.received.elapsed() < ANR_MERGE_WINDOW
+                && (package.is_empty() || r.package == package)
+        });
+        match recent {
+            Some(report) => {
+                report.trace.push_str(&format!("\n\n--- {} ---\n{}", file, trace));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+struct Watcher {
+    stop: Arc<AtomicBool>,
+    alive: Arc<AtomicBool>,
+    started: Instant,
+}
+
+// Lines of one crash being collected until its process goes quiet
+struct PendingCrash {
+    kind: CrashKind,
+    pid: Option<u32>,
+    tag: String,
+    lines: Vec<String>,
+    last_line: Instant,
+}
+
+impl PendingCrash {
+    fn new(kind: CrashKind, entry: &LogEntry) -> Self {
+        Self {
+            kind,
+            pid: entry.pid,
+            tag: entry.tag.clone(),
+            lines: vec![entry.message.clone()],
+            last_line: Instant::now(),
+        }
+    }
+
+    fn continues_with(&self, entry: &LogEntry) -> bool {
+        entry.pid == self.pid && entry.tag == self.tag
+    }
+
+    fn package(&self) -> String {
+        for line in &self.lines {
+            // Java: "Process: com.example.app, PID: 1234"
+            if let Some(rest) = line.strip_prefix("Process: ") {
+                return rest.split(',').next().unwrap_or_default().trim().to_string();
+            }
+            // Native: "pid: 1234, tid: 1250, name: RenderThread  >>> com.example.app <<<"
+            if let Some((_, rest)) = line.split_once(">>> ") {
+                return rest.split(" <<<").next().unwrap_or_default().trim().to_string();
+            }
+        }
+        String::new()
+    }
+
+    fn summary(&self) -> String {
+        let line = match self.kind {
+            // The exception line follows "FATAL EXCEPTION: main" and "Process: ..."
+            CrashKind::JavaCrash => self.lines.iter().skip(1).find(|l| !l.starts_with("Process: ")),
+            CrashKind::NativeCrash => self.lines.iter().find(|l| l.starts_with("signal ") || l.starts_with("Abort message")),
+            CrashKind::Anr => None,
+        };
+        line.or(self.lines.first()).cloned().unwrap_or_default()
+    }
+}
+
+// Watches every usable device for FATAL EXCEPTIONs, native crashes (tombstones) and ANRs:
+// `logcat -b crash -b events` for the crashes and am_anr events, plus /data/anr for trace files
+pub struct CrashMonitor {
+    adb_manager: Arc<AdbManager>,
+    ctx: egui::Context,
+    state: Arc<Mutex<MonitorState>>,
+    watchers: HashMap<String, Watcher>,
+    enabled: bool,
+}
+
+impl CrashMonitor {
+    pub fn start(adb_manager: Arc<AdbManager>, ctx: egui::Context) -> Self {
+        Self {
+            adb_manager,
+            ctx,
+            state: Arc::new(Mutex::new(MonitorState::default())),
+            watchers: HashMap::new(),
+            enabled: false,
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            for (_, watcher) in self.watchers.drain() {
+                watcher.stop.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn set_watched_packages(&self, packages: Vec<String>) {
+        self.state.lock().unwrap().watched_packages = packages;
+    }
+
+    // Starts watchers for new devices and stops those of devices that went away
+    pub fn sync_devices(&mut self, devices: &[Device]) {
+        if !self.enabled {
+            return;
+        }
+        let usable: HashSet<&str> = devices.iter().filter(|d| d.is_usable()).map(|d| d.id.as_str()).collect();
+        self.watchers.retain(|device_id, watcher| {
+            let keep = usable.contains(device_id.as_str())
+                && (watcher.alive.load(Ordering::Relaxed) || watcher.started.elapsed() < RESTART_INTERVAL);
+            if !keep {
+                watcher.stop.store(true, Ordering::Relaxed);
+            }
+            keep
+        });
+
+        for device_id in usable {
+            if !self.watchers.contains_key(device_id) {
+                let watcher = self.spawn_watcher(device_id);
+                self.watchers.insert(device_id.to_string(), watcher);
+            }
+        }
+    }
+
+    pub fn watched_devices(&self) -> usize {
+        self.watchers.values().filter(|w| w.alive.load(Ordering::Relaxed)).count()
+    }
+
+    pub fn reports(&self) -> Vec<CrashReport> {
+        self.state.lock().unwrap().reports.iter().cloned().collect()
+    }
+
+    pub fn unseen_count(&self) -> usize {
+        self.state.lock().unwrap().reports.iter().filter(|r| !r.seen).count()
+    }
+
+    pub fn mark_seen(&self, id: u64) {
+        if let Some(report) = self.state.lock().unwrap().reports.iter_mut().find(|r| r.id == id) {
+            report.seen = true;
+        }
+    }
+
+    pub fn clear(&self) {
+        self.state.lock().unwrap().reports.clear();
+    }
+
+    fn spawn_watcher(&self, device_id: &str) -> Watcher {
+        let stop = Arc::new(AtomicBool::new(false));
+        let alive = Arc::new(AtomicBool::new(true));
+
+        let adb_manager = self.adb_manager.clone();
+        let state = self.state.clone();
+        let ctx = self.ctx.clone();
+        let device_id = device_id.to_string();
+        let thread_stop = stop.clone();
+        let thread_alive = alive.clone();
+        thread::spawn(move || {
+            watch_device(&adb_manager, &device_id, &state, &thread_stop, &ctx);
+            thread_alive.store(false, Ordering::Relaxed);
+        });
+
+        Watcher {
+            stop,
+            alive,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Drop for CrashMonitor {
+    fn drop(&mut self) {
+        self.set_enabled(false);
+    }
+}
+
+fn watch_device(adb_manager: &AdbManager, device_id: &str, state: &Mutex<MonitorState>, stop: &AtomicBool, ctx: &egui::Context) {
+    // Start at the device's current time so crashes from before the app started are not reported again
+    let since = adb_manager
+        .run_adb_command(&["-s", device_id, "shell", "date", "+%s"])
+        .map(|output| output.trim().to_string())
+        .ok()
+        .filter(|t| t.parse::<u64>().is_ok())
+        // logcat takes "<seconds>.<millis>" as a time; a bare number would be a line count
+        .map(|t| format!("{}.000", t))
+        .unwrap_or_else(|| "1".to_string());
+    let stream = match LogcatStream::spawn(adb_manager, device_id, &[LogBuffer::Crash, LogBuffer::Events], &["-T", &since], None) {
+        Ok(stream) => stream,
+        Err(_) => return,
+    };
+
+    let mut anr_files = list_anr_files(adb_manager, device_id).unwrap_or_default();
+    let mut anr_checked = Instant::now();
+    let mut pending: Option<PendingCrash> = None;
+
+    while !stop.load(Ordering::Relaxed) {
+        let entry = match stream.recv_timeout(Duration::from_millis(500)) {
+            Ok(line) => Some(LogEntry::parse(&line)),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        if let Some(entry) = &entry {
+            match &mut pending {
+                Some(crash) if crash.continues_with(entry) => {
+                    crash.lines.push(entry.message.clone());
+                    crash.last_line = Instant::now();
+                    continue;
+                }
+                _ => {}
+            }
+        }
+
+        let finished = match &pending {
+            Some(crash) => entry.is_some() || crash.last_line.elapsed() >= TRACE_IDLE,
+            None => false,
+        };
+        if finished {
+            if let Some(crash) = pending.take() {
+                state.lock().unwrap().report(device_id, crash.kind, crash.package(), crash.summary(), crash.lines.join("\n"));
+                ctx.request_repaint();
+            }
+        }
+
+        if let Some(entry) = entry {
+            if entry.tag == "AndroidRuntime" && entry.message.starts_with("FATAL EXCEPTION") {
+                pending = Some(PendingCrash::new(CrashKind::JavaCrash, &entry));
+            } else if entry.tag == "DEBUG" && entry.message.starts_with("*** *** ***") {
+                pending = Some(PendingCrash::new(CrashKind::NativeCrash, &entry));
+            } else if entry.tag == "am_anr" {
+                let (package, reason) = parse_am_anr(&entry.message);
+                state.lock().unwrap().report(device_id, CrashKind::Anr, package, reason, entry.line.clone());
+                ctx.request_repaint();
+            }
+        }
+
+        if anr_checked.elapsed() >= ANR_DIR_INTERVAL {
+            anr_checked = Instant::now();
+            if let Ok(files) = list_anr_files(adb_manager, device_id) {
+                for file in files.iter().filter(|f| !anr_files.contains(*f)) {
+                    report_anr_file(adb_manager, device_id, file, state);
+                    ctx.request_repaint();
+                }
+                anr_files = files;
+            }
+        }
+    }
+}
+
+// Package and reason of an am_anr event: "[user,pid,package,flags,reason]"
+fn parse_am_anr(message: &str) -> (String, String) {
+    let fields: Vec<&str> = message.trim_matches(['[', ']']).splitn(5, ',').collect();
+    let package = fields.get(2).copied().unwrap_or_default().to_string();
+    let reason = fields.get(4).copied().unwrap_or("Application Not Responding").to_string();
+    (package, reason)
+}
+
+// Fails without root on most user builds; the am_anr events still catch the ANRs then
+fn list_anr_files(adb_manager: &AdbManager, device_id: &str) -> Result<HashSet<String>, String> {
+    let output = adb_manager.run_adb_command(&["-s", device_id, "shell", "ls", "/data/anr"])?;
+    Ok(output.split_whitespace().map(str::to_string).collect())
+}
+
+fn report_anr_file(adb_manager: &AdbManager, device_id: &str, file: &str, state: &Mutex<MonitorState>) {
+    let path = format!("/data/anr/{}", file);
+    let trace = match adb_manager.run_adb_command(&["-s", device_id, "shell", "cat", &path]) {
+        Ok(content) => content.lines().take(MAX_ANR_TRACE_LINES).collect::<Vec<_>>().join("\n"),
+        Err(e) => format!("Could not read {}: {}", path, e.trim()),
+    };
+    // "Cmd line: com.example.app" names the process the trace belongs to
+    let package = trace
+        .lines()
+        .find_map(|l| l.strip_prefix("Cmd line: "))
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+
+    let mut state = state.lock().unwrap();
+    if !state.attach_anr_trace(device_id, &package, &path, &trace) {
+        state.report(device_id, CrashKind::Anr, package, format!("New ANR trace {}", path), trace);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Collects lines the way watch_device does, starting a crash at the first one
+    fn collect(kind: CrashKind, lines: &[&str]) -> PendingCrash {
+        let mut crash = PendingCrash::new(kind, &LogEntry::parse(lines[0]));
+        for line in &lines[1..] {
+            let entry = LogEntry::parse(line);
+            if crash.continues_with(&entry) {
+                crash.lines.push(entry.message);
+            }
+        }
+        crash
+    }
+
+    #[test]
+    fn java_crash_package_and_exception() {
+        let crash = collect(
+            CrashKind::JavaCrash,
+            &[
+                "10-19 12:34:56.789  4321  4321 E AndroidRuntime: FATAL EXCEPTION: main",
+                "10-19 12:34:56.789  4321  4321 E AndroidRuntime: Process: com.example.app, PID: 4321",
+                "10-19 12:34:56.789  4321  4321 E AndroidRuntime: java.lang.NullPointerException: Attempt to invoke virtual method",
+                "10-19 12:34:56.790  1000  1020 W ActivityManager: Force finishing activity com.example.app/.MainActivity",
+                "10-19 12:34:56.790  4321  4321 E AndroidRuntime: \tat com.example.app.MainActivity.onCreate(MainActivity.kt:42)",
+            ],
+        );
+        assert_eq!(crash.package(), "com.example.app");
+        assert_eq!(crash.summary(), "java.lang.NullPointerException: Attempt to invoke virtual method");
+        assert_eq!(crash.lines.len(), 4);
+    }
+
+    #[test]
+    fn native_crash_package_and_signal() {
+        let crash = collect(
+            CrashKind::NativeCrash,
+            &[
+                "10-19 12:34:56.789  5555  5555 F DEBUG   : *** *** *** *** *** *** *** *** *** *** *** *** *** *** *** ***",
+                "10-19 12:34:56.789  5555  5555 F DEBUG   : Build fingerprint: 'google/sdk_gphone64_x86_64/emu64x:14/UE1A/1:userdebug/dev-keys'",
+                "10-19 12:34:56.789  5555  5555 F DEBUG   : pid: 4321, tid: 4350, name: RenderThread  >>> com.example.game <<<",
+                "10-19 12:34:56.789  5555  5555 F DEBUG   : signal 11 (SIGSEGV), code 1 (SEGV_MAPERR), fault addr 0x0",
+            ],
+        );
+        assert_eq!(crash.package(), "com.example.game");
+        assert_eq!(crash.summary(), "signal 11 (SIGSEGV), code 1 (SEGV_MAPERR), fault addr 0x0");
+    }
+
+    #[test]
+    fn crash_without_package_falls_back_to_first_line() {
+        let crash = collect(CrashKind::NativeCrash, &["10-19 12:34:56.789  5555  5555 F DEBUG   : *** *** ***"]);
+        assert_eq!(crash.package(), "");
+        assert_eq!(crash.summary(), "*** *** ***");
+    }
+
+    #[test]
+    fn parses_am_anr_events() {
+        let entry = LogEntry::parse(
+            "10-19 12:34:56.789  1000  1050 I am_anr  : [0,4321,com.example.app,952745542,Input dispatching timed out (Waiting to send key event)]",
+        );
+        assert_eq!(entry.tag, "am_anr");
+        let (package, reason) = parse_am_anr(&entry.message);
+        assert_eq!(package, "com.example.app");
+        assert_eq!(reason, "Input dispatching timed out (Waiting to send key event)");
+
+        // Reasons may contain commas of their own
+        let (_, reason) = parse_am_anr("[0,4321,com.example.app,952745542,executing service com.example.app/.SyncService, 20s]");
+        assert_eq!(reason, "executing service com.example.app/.SyncService, 20s");
+
+        assert_eq!(parse_am_anr("[0,4321]"), (String::new(), "Application Not Responding".to_string()));
+    }
+}
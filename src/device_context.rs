@@ -0,0 +1,217 @@
+use eframe::egui;
+use std::collections::HashSet;
+use crate::adb::Device;
+use crate::device_labels::DeviceLabels;
+use crate::device_tracker::DeviceTracker;
+
+#[derive(Clone, PartialEq)]
+struct SelectedDevice {
+    id: String,
+    // Lets the selection follow a wireless device that comes back on a different port
+    serial: Option<String>,
+}
+
+#[derive(Default)]
+pub struct DeviceSelection {
+    active: Option<SelectedDevice>,
+    set: Vec<SelectedDevice>,
+}
+
+impl DeviceSelection {
+    pub fn active(&self) -> Option<&str> {
+        self.active.as_ref().map(|d| d.id.as_str())
+    }
+
+    pub fn is_in_set(&self, device_id: &str) -> bool {
+        self.set.iter().any(|d| d.id == device_id)
+    }
+
+    fn select(device_id: &str, labels: &DeviceLabels) -> SelectedDevice {
+        SelectedDevice {
+            id: device_id.to_string(),
+            serial: labels.hardware_serial(device_id).map(str::to_string),
+        }
+    }
+
+    fn rebind(selected: &mut SelectedDevice, devices: &[Device], labels: &DeviceLabels) {
+        if devices.iter().any(|d| d.id == selected.id) {
+            if selected.serial.is_none() {
+                selected.serial = labels.hardware_serial(&selected.id).map(str::to_string);
+            }
+            return;
+        }
+        if let Some(serial) = &selected.serial {
+            if let Some(device) = devices.iter().find(|d| labels.hardware_serial(&d.id) == Some(serial)) {
+                selected.id = device.id.clone();
+            }
+        }
+    }
+
+    fn update(&mut self, devices: &[Device], labels: &DeviceLabels) {
+        if let Some(active) = &mut self.active {
+            Self::rebind(active, devices, labels);
+        }
+        for selected in &mut self.set {
+            Self::rebind(selected, devices, labels);
+        }
+        // Two entries can rebind to the same device, keep the first
+        let mut seen = HashSet::new();
+        self.set.retain(|d| seen.insert(d.id.clone()));
+    }
+}
+
+// Device state shared by every tab: the tracked device list, user labels and the selection
+pub struct DeviceContext {
+    tracker: DeviceTracker,
+    pub devices: Vec<Device>,
+    // Devices that became usable and have not been handed out by take_connected yet
+    connected: Vec<Device>,
+    pub labels: DeviceLabels,
+    pub selection: DeviceSelection,
+}
+
+impl DeviceContext {
+    pub fn new(tracker: DeviceTracker, labels: DeviceLabels) -> Self {
+        Self {
+            tracker,
+            devices: Vec::new(),
+            connected: Vec::new(),
+            labels,
+            selection: DeviceSelection::default(),
+        }
+    }
+
+    pub fn update(&mut self) {
+        let previous = std::mem::replace(&mut self.devices, self.tracker.devices());
+        let connected: Vec<Device> = self
+            .devices
+            .iter()
+            .filter(|d| d.is_usable() && !previous.iter().any(|p| p.id == d.id && p.is_usable()))
+            .cloned()
+            .collect();
+        self.connected.extend(connected);
+        self.labels.update_serials(self.tracker.hardware_serials());
+        self.selection.update(&self.devices, &self.labels);
+
+        // Pick the first usable device so single-device tabs work without a trip to the selector
+        if self.selection.active.is_none() {
+            if let Some(device) = self.devices.iter().find(|d| d.is_usable()) {
+                self.selection.active = Some(DeviceSelection::select(&device.id, &self.labels));
+            }
+        }
+    }
+
+    pub fn refresh(&mut self) {
+        self.tracker.refresh();
+        self.update();
+    }
+
+    pub fn take_connected(&mut self) -> Vec<Device> {
+        std::mem::take(&mut self.connected)
+    }
+
+    pub fn set_active(&mut self, device_id: &str) {
+        self.selection.active = Some(DeviceSelection::select(device_id, &self.labels));
+    }
+
+    pub fn toggle_in_set(&mut self, device_id: &str) {
+        if self.selection.is_in_set(device_id) {
+            self.selection.set.retain(|d| d.id != device_id);
+        } else {
+            let selected = DeviceSelection::select(device_id, &self.labels);
+            self.selection.set.push(selected);
+        }
+    }
+
+    pub fn active_device(&self) -> Option<&Device> {
+        let active = self.selection.active()?;
+        self.devices.iter().find(|d| d.id == active)
+    }
+
+    // Devices a multi-device action applies to: the selected set, or the active device when the set is empty
+    pub fn target_devices(&self) -> Vec<Device> {
+        if self.selection.set.is_empty() {
+            self.active_device().cloned().into_iter().collect()
+        } else {
+            self.devices
+                .iter()
+                .filter(|d| self.selection.is_in_set(&d.id))
+                .cloned()
+                .collect()
+        }
+    }
+
+    pub fn device_label(&self, device: &Device) -> egui::RichText {
+        let name = if device.is_usable() {
+            self.labels.display_name(&device.id)
+        } else {
+            format!("{} ({})", self.labels.display_name(&device.id), device.state)
+        };
+        self.labels.rich_text(&device.id, name)
+    }
+
+    // Combo box picking the active device; unusable devices are listed but greyed out
+    pub fn device_picker(&mut self, ui: &mut egui::Ui, id_source: &str) {
+        let selected_text = match self.selection.active() {
+            Some(active) => match self.devices.iter().find(|d| d.id == active) {
+                Some(device) => self.device_label(device),
+                None => egui::RichText::new(format!("{} (disconnected)", self.labels.display_name(active))),
+            },
+            None => egui::RichText::new("No device"),
+        };
+
+        let mut picked = None;
+        egui::ComboBox::from_id_source(id_source)
+            .selected_text(selected_text)
+            .show_ui(ui, |ui| {
+                for device in &self.devices {
+                    let response = ui
+                        .add_enabled(
+                            device.is_usable(),
+                            egui::SelectableLabel::new(self.selection.active() == Some(device.id.as_str()), self.device_label(device)),
+                        )
+                        .on_disabled_hover_text(device.state_hint().unwrap_or_default());
+                    if response.clicked() {
+                        picked = Some(device.id.clone());
+                    }
+                }
+            });
+
+        if let Some(device_id) = picked {
+            self.set_active(&device_id);
+        }
+    }
+
+    pub fn show_selector(&mut self, ui: &mut egui::Ui) {
+        ui.label("Device:");
+        self.device_picker(ui, "active_device");
+
+        let title = if self.selection.set.is_empty() {
+            "Multi-select".to_string()
+        } else {
+            format!("Multi-select ({})", self.selection.set.len())
+        };
+        ui.menu_button(title, |ui| {
+            ui.label("Devices for multi-device actions:");
+            for device in self.devices.clone() {
+                let mut checked = self.selection.is_in_set(&device.id);
+                let checkbox = egui::Checkbox::new(&mut checked, self.device_label(&device));
+                if ui.add_enabled(device.is_usable(), checkbox).changed() {
+                    self.toggle_in_set(&device.id);
+                }
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("All usable").clicked() {
+                    self.selection.set.clear();
+                    for device in self.devices.clone().iter().filter(|d| d.is_usable()) {
+                        self.toggle_in_set(&device.id);
+                    }
+                }
+                if ui.button("Clear").clicked() {
+                    self.selection.set.clear();
+                }
+            });
+        });
+    }
+}
@@ -0,0 +1,111 @@
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+const SETTINGS_KEY: &str = "device_labels";
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceLabel {
+    pub nickname: String,
+    pub color: Option<[u8; 3]>,
+    pub group: String,
+}
+
+impl DeviceLabel {
+    fn is_empty(&self) -> bool {
+        self.nickname.is_empty() && self.color.is_none() && self.group.is_empty()
+    }
+}
+
+// User-defined names for devices. Labels are keyed by the hardware serial so a phone keeps its
+// label whether it is attached over USB or over Wi-Fi with a different address every time.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceLabels {
+    labels: HashMap<String, DeviceLabel>,
+    // Device id -> ro.serialno
+    #[serde(skip)]
+    hardware_serials: HashMap<String, String>,
+}
+
+impl DeviceLabels {
+    pub fn load(storage: &dyn eframe::Storage) -> Self {
+        eframe::get_value(storage, SETTINGS_KEY).unwrap_or_default()
+    }
+
+    pub fn save(&self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, SETTINGS_KEY, self);
+    }
+
+    // Takes the serials found by the device tracker; ids it no longer lists are forgotten, so a
+    // phone that later gets the same wireless address does not inherit another phone's label
+    pub fn update_serials(&mut self, serials: HashMap<String, String>) {
+        self.hardware_serials.retain(|device_id, _| serials.contains_key(device_id));
+        for (device_id, serial) in serials {
+            if self.hardware_serials.get(&device_id) != Some(&serial) {
+                self.set_hardware_serial(&device_id, serial);
+            }
+        }
+    }
+
+    // A label set while only the device id was known moves to the serial key
+    fn set_hardware_serial(&mut self, device_id: &str, serial: String) {
+        if let Some(label) = self.labels.remove(device_id) {
+            self.labels.entry(serial.clone()).or_insert(label);
+        }
+        self.hardware_serials.insert(device_id.to_string(), serial);
+    }
+
+    pub fn hardware_serial(&self, device_id: &str) -> Option<&str> {
+        self.hardware_serials.get(device_id).map(String::as_str)
+    }
+
+    // Stable key for per-device settings: the hardware serial, or the device id until it is known
+    pub fn key(&self, device_id: &str) -> String {
+        self.hardware_serial(device_id).unwrap_or(device_id).to_string()
+    }
+
+    pub fn get(&self, device_id: &str) -> Option<&DeviceLabel> {
+        self.labels.get(&self.key(device_id))
+    }
+
+    pub fn set(&mut self, device_id: &str, label: DeviceLabel) {
+        let key = self.key(device_id);
+        if label.is_empty() {
+            self.labels.remove(&key);
+        } else {
+            self.labels.insert(key, label);
+        }
+    }
+
+    pub fn groups(&self) -> Vec<String> {
+        self.labels
+            .values()
+            .filter(|l| !l.group.is_empty())
+            .map(|l| l.group.clone())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    pub fn group(&self, device_id: &str) -> &str {
+        self.get(device_id).map_or("", |l| l.group.as_str())
+    }
+
+    pub fn display_name(&self, device_id: &str) -> String {
+        match self.get(device_id) {
+            Some(label) if !label.nickname.is_empty() => format!("{} ({})", label.nickname, device_id),
+            _ => device_id.to_string(),
+        }
+    }
+
+    // Display name tinted with the device color, for use in labels and pickers
+    pub fn rich_text(&self, device_id: &str, text: String) -> egui::RichText {
+        let text = egui::RichText::new(text);
+        match self.get(device_id).and_then(|l| l.color) {
+            Some([r, g, b]) => text.color(egui::Color32::from_rgb(r, g, b)),
+            None => text,
+        }
+    }
+}
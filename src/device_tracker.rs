@@ -0,0 +1,97 @@
+use eframe::egui;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use crate::adb::{AdbManager, Device, DeviceState};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+// Polls `adb devices` in the background so every tab sees the same, current device list.
+// The hardware serial (ro.serialno) of each usable device is looked up on the same thread.
+pub struct DeviceTracker {
+    adb_manager: Arc<AdbManager>,
+    devices: Arc<Mutex<Vec<Device>>>,
+    // Device id -> ro.serialno
+    hardware_serials: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl DeviceTracker {
+    pub fn start(adb_manager: Arc<AdbManager>, ctx: egui::Context) -> Self {
+        let devices = Arc::new(Mutex::new(Vec::new()));
+        let hardware_serials = Arc::new(Mutex::new(HashMap::new()));
+
+        let thread_adb = adb_manager.clone();
+        let thread_devices = devices.clone();
+        let thread_serials = hardware_serials.clone();
+        thread::spawn(move || {
+            // Device id -> state it was in when the lookup failed; retried once the state changes
+            let mut failed_lookups: HashMap<String, DeviceState> = HashMap::new();
+            loop {
+                if let Ok(current) = thread_adb.list_devices() {
+                    let mut devices = thread_devices.lock().unwrap();
+                    if *devices != current {
+                        *devices = current.clone();
+                        ctx.request_repaint();
+                    }
+                    drop(devices);
+
+                    if resolve_serials(&thread_adb, &current, &thread_serials, &mut failed_lookups) {
+                        ctx.request_repaint();
+                    }
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+        });
+
+        Self { adb_manager, devices, hardware_serials }
+    }
+
+    pub fn devices(&self) -> Vec<Device> {
+        self.devices.lock().unwrap().clone()
+    }
+
+    pub fn hardware_serials(&self) -> HashMap<String, String> {
+        self.hardware_serials.lock().unwrap().clone()
+    }
+
+    // Polls immediately, for use right after an action that changes the device list
+    pub fn refresh(&self) {
+        if let Ok(current) = self.adb_manager.list_devices() {
+            *self.devices.lock().unwrap() = current;
+        }
+    }
+}
+
+// Looks up ro.serialno for usable devices seen for the first time and forgets devices that left
+// the list, as another phone may later reuse a wireless address; true when the map changed
+fn resolve_serials(
+    adb_manager: &AdbManager,
+    devices: &[Device],
+    hardware_serials: &Mutex<HashMap<String, String>>,
+    failed_lookups: &mut HashMap<String, DeviceState>,
+) -> bool {
+    failed_lookups.retain(|id, _| devices.iter().any(|d| &d.id == id));
+    let mut changed = {
+        let mut serials = hardware_serials.lock().unwrap();
+        let known = serials.len();
+        serials.retain(|id, _| devices.iter().any(|d| &d.id == id));
+        serials.len() != known
+    };
+    for device in devices.iter().filter(|d| d.is_usable()) {
+        if hardware_serials.lock().unwrap().contains_key(&device.id) || failed_lookups.get(&device.id) == Some(&device.state) {
+            continue;
+        }
+        match adb_manager.get_prop(&device.id, "ro.serialno") {
+            Ok(serial) if !serial.is_empty() => {
+                failed_lookups.remove(&device.id);
+                hardware_serials.lock().unwrap().insert(device.id.clone(), serial);
+                changed = true;
+            }
+            _ => {
+                failed_lookups.insert(device.id.clone(), device.state.clone());
+            }
+        }
+    }
+    changed
+}
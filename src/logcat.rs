@@ -0,0 +1,419 @@
+use eframe::egui;
+use regex::{Regex, RegexBuilder};
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::process::Child;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+use crate::adb::AdbManager;
+
+// Lines arriving closer together than this share one repaint
+const REPAINT_INTERVAL: Duration = Duration::from_millis(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum LogLevel {
+    Verbose,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 6] = [
+        LogLevel::Verbose,
+        LogLevel::Debug,
+        LogLevel::Info,
+        LogLevel::Warn,
+        LogLevel::Error,
+        LogLevel::Fatal,
+    ];
+
+    fn parse(letter: &str) -> Option<Self> {
+        match letter {
+            "V" => Some(LogLevel::Verbose),
+            "D" => Some(LogLevel::Debug),
+            "I" => Some(LogLevel::Info),
+            "W" => Some(LogLevel::Warn),
+            "E" => Some(LogLevel::Error),
+            // "S" (silent) never appears on a line, "A" is assert which logcat shows as F
+            "F" | "A" => Some(LogLevel::Fatal),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LogLevel::Verbose => "Verbose",
+            LogLevel::Debug => "Debug",
+            LogLevel::Info => "Info",
+            LogLevel::Warn => "Warn",
+            LogLevel::Error => "Error",
+            LogLevel::Fatal => "Fatal",
+        }
+    }
+
+    pub fn color(&self) -> egui::Color32 {
+        match self {
+            LogLevel::Verbose => egui::Color32::GRAY,
+            LogLevel::Debug => egui::Color32::from_rgb(110, 160, 230),
+            LogLevel::Info => egui::Color32::from_rgb(100, 200, 100),
+            LogLevel::Warn => egui::Color32::from_rgb(230, 180, 60),
+            LogLevel::Error => egui::Color32::from_rgb(240, 80, 80),
+            LogLevel::Fatal => egui::Color32::from_rgb(220, 80, 220),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogBuffer {
+    Main,
+    System,
+    Crash,
+    Events,
+}
+
+impl LogBuffer {
+    pub const ALL: [LogBuffer; 4] = [LogBuffer::Main, LogBuffer::System, LogBuffer::Crash, LogBuffer::Events];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LogBuffer::Main => "main",
+            LogBuffer::System => "system",
+            LogBuffer::Crash => "crash",
+            LogBuffer::Events => "events",
+        }
+    }
+}
+
+// One logcat line. Lines that are not in threadtime format, like "--------- beginning of main",
+// keep only `line` and have no level.
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub line: String,
+    pub date: String,
+    pub time: String,
+    pub pid: Option<u32>,
+    pub tid: Option<u32>,
+    pub level: Option<LogLevel>,
+    pub tag: String,
+    pub message: String,
+}
+
+impl LogEntry {
+    // "10-19 12:34:56.789  1234  5678 I Tag     : message"
+    pub fn parse(line: &str) -> Self {
+        Self::parse_threadtime(line).unwrap_or_else(|| Self {
+            line: line.to_string(),
+            date: String::new(),
+            time: String::new(),
+            pid: None,
+            tid: None,
+            level: None,
+            tag: String::new(),
+            message: line.to_string(),
+        })
+    }
+
+    fn parse_threadtime(line: &str) -> Option<Self> {
+        let mut rest = line;
+        let mut fields = Vec::with_capacity(5);
+        for _ in 0..5 {
+            rest = rest.trim_start();
+            let end = rest.find(' ')?;
+            fields.push(&rest[..end]);
+            rest = &rest[end..];
+        }
+        let rest = rest.trim_start();
+        let (tag, message) = match rest.split_once(": ") {
+            Some((tag, message)) => (tag, message),
+            None => (rest.strip_suffix(':')?, ""),
+        };
+
+        Some(Self {
+            line: line.to_string(),
+            date: fields[0].to_string(),
+            time: fields[1].to_string(),
+            pid: Some(fields[2].parse().ok()?),
+            tid: Some(fields[3].parse().ok()?),
+            level: Some(LogLevel::parse(fields[4])?),
+            tag: tag.trim_end().to_string(),
+            message: message.to_string(),
+        })
+    }
+
+    pub fn color(&self) -> egui::Color32 {
+        self.level.map_or(egui::Color32::GRAY, |l| l.color())
+    }
+
+    // One object per line for JSON lines export; unparsed lines only carry a message
+    pub fn to_json(&self) -> serde_json::Value {
+        if self.level.is_none() {
+            return serde_json::json!({ "message": self.message });
+        }
+        serde_json::json!({
+            "date": self.date,
+            "time": self.time,
+            "pid": self.pid,
+            "tid": self.tid,
+            "level": self.level.map(|l| l.label()),
+            "tag": self.tag,
+            "message": self.message,
+        })
+    }
+}
+
+// Reads a saved `-v threadtime` log, e.g. one recorded by the Logcat tab or attached to a bug report
+pub fn read_log_file(path: &Path) -> Result<Vec<LogEntry>, String> {
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    Ok(String::from_utf8_lossy(&bytes)
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(LogEntry::parse)
+        .collect())
+}
+
+// Filters shared by the live view and saved logs
+pub struct LogFilter {
+    pub min_level: LogLevel,
+    pub tag: String,
+    pub pid: String,
+    // Process ids of the package filter, looked up on the device
+    pub package_pids: Option<Vec<u32>>,
+    pub search: String,
+    pub use_regex: bool,
+    pub case_sensitive: bool,
+    regex: Option<Regex>,
+    pub regex_error: Option<String>,
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        Self {
+            min_level: LogLevel::Verbose,
+            tag: String::new(),
+            pid: String::new(),
+            package_pids: None,
+            search: String::new(),
+            use_regex: false,
+            case_sensitive: false,
+            regex: None,
+            regex_error: None,
+        }
+    }
+}
+
+impl LogFilter {
+    // Call after changing `search`, `use_regex` or `case_sensitive`
+    pub fn compile(&mut self) {
+        self.regex = None;
+        self.regex_error = None;
+        if self.search.is_empty() {
+            return;
+        }
+        let pattern = if self.use_regex {
+            self.search.clone()
+        } else {
+            regex::escape(&self.search)
+        };
+        match RegexBuilder::new(&pattern).case_insensitive(!self.case_sensitive).build() {
+            Ok(regex) => self.regex = Some(regex),
+            Err(e) => self.regex_error = Some(e.to_string()),
+        }
+    }
+
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        if entry.level.is_some_and(|l| l < self.min_level) {
+            return false;
+        }
+        if !self.tag.is_empty() && !entry.tag.to_lowercase().contains(&self.tag.to_lowercase()) {
+            return false;
+        }
+        if !self.pid.trim().is_empty() && entry.pid.map(|p| p.to_string()).as_deref() != Some(self.pid.trim()) {
+            return false;
+        }
+        if let Some(pids) = &self.package_pids {
+            if !entry.pid.is_some_and(|p| pids.contains(&p)) {
+                return false;
+            }
+        }
+        match &self.regex {
+            Some(regex) => regex.is_match(&entry.tag) || regex.is_match(&entry.message),
+            // An invalid pattern hides nothing rather than everything
+            None => true,
+        }
+    }
+}
+
+// A running `adb logcat` process whose lines arrive on a channel
+pub struct LogcatStream {
+    child: Child,
+    lines: Receiver<String>,
+    stderr: Option<thread::JoinHandle<String>>,
+}
+
+impl LogcatStream {
+    pub fn start(adb_manager: &AdbManager, device: &str, buffers: &[LogBuffer], ctx: egui::Context) -> Result<Self, String> {
+        Self::spawn(adb_manager, device, buffers, &[], Some(ctx))
+    }
+
+    // `extra_args` go after the buffers, e.g. `-T <time>`. Without `ctx` nothing is repainted,
+    // for background readers that only look at the lines.
+    pub fn spawn(
+        adb_manager: &AdbManager,
+        device: &str,
+        buffers: &[LogBuffer],
+        extra_args: &[&str],
+        ctx: Option<egui::Context>,
+    ) -> Result<Self, String> {
+        let mut args = vec!["-s", device, "logcat", "-v", "threadtime"];
+        for buffer in buffers {
+            args.push("-b");
+            args.push(buffer.name());
+        }
+        args.extend_from_slice(extra_args);
+        let mut child = adb_manager.spawn_adb_command(&args)?;
+
+        let stdout = child.stdout.take().ok_or("logcat has no output")?;
+        let stderr = child.stderr.take().map(|stderr| {
+            thread::spawn(move || {
+                BufReader::new(stderr).lines().map_while(Result::ok).collect::<Vec<_>>().join("\n")
+            })
+        });
+
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            // Read raw bytes: apps log invalid UTF-8 often enough that `lines()` would end the stream
+            let mut reader = BufReader::new(stdout);
+            let mut buffer = Vec::new();
+            while matches!(reader.read_until(b'\n', &mut buffer), Ok(n) if n > 0) {
+                let line = String::from_utf8_lossy(&buffer).trim_end_matches(['\r', '\n']).to_string();
+                buffer.clear();
+                if sender.send(line).is_err() {
+                    break;
+                }
+                if let Some(ctx) = &ctx {
+                    ctx.request_repaint_after(REPAINT_INTERVAL);
+                }
+            }
+            if let Some(ctx) = &ctx {
+                ctx.request_repaint();
+            }
+        });
+
+        Ok(Self { child, lines, stderr })
+    }
+
+    pub fn try_recv(&self) -> impl Iterator<Item = String> + '_ {
+        self.lines.try_iter()
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<String, RecvTimeoutError> {
+        self.lines.recv_timeout(timeout)
+    }
+
+    // Some(message) once the process has exited
+    pub fn exit_message(&mut self) -> Option<String> {
+        let status = self.child.try_wait().ok()??;
+        let stderr = self.stderr.take().and_then(|h| h.join().ok()).unwrap_or_default();
+        Some(if stderr.trim().is_empty() {
+            format!("logcat exited ({})", status)
+        } else {
+            format!("logcat exited: {}", stderr.trim())
+        })
+    }
+}
+
+impl Drop for LogcatStream {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_threadtime_lines() {
+        let entry = LogEntry::parse("10-19 12:34:56.789  1234  5678 I ActivityManager: Start proc 4321:com.example/u0a123");
+        assert_eq!(entry.date, "10-19");
+        assert_eq!(entry.time, "12:34:56.789");
+        assert_eq!(entry.pid, Some(1234));
+        assert_eq!(entry.tid, Some(5678));
+        assert_eq!(entry.level, Some(LogLevel::Info));
+        assert_eq!(entry.tag, "ActivityManager");
+        assert_eq!(entry.message, "Start proc 4321:com.example/u0a123");
+    }
+
+    #[test]
+    fn keeps_non_threadtime_lines_whole() {
+        for line in ["--------- beginning of main", "", "10-19 garbage", "10-19 12:34:56.789 pid 5678 I Tag: message"] {
+            let entry = LogEntry::parse(line);
+            assert_eq!(entry.level, None);
+            assert_eq!(entry.pid, None);
+            assert_eq!(entry.message, line);
+            assert_eq!(entry.line, line);
+        }
+    }
+
+    #[test]
+    fn tags_may_contain_spaces() {
+        let entry = LogEntry::parse("10-19 12:34:56.789  1234  1234 W Some Tag : message: with colon");
+        assert_eq!(entry.tag, "Some Tag");
+        assert_eq!(entry.message, "message: with colon");
+    }
+
+    #[test]
+    fn tag_with_empty_message() {
+        let entry = LogEntry::parse("10-19 12:34:56.789  1234  1234 D Tag:");
+        assert_eq!(entry.level, Some(LogLevel::Debug));
+        assert_eq!(entry.tag, "Tag");
+        assert_eq!(entry.message, "");
+    }
+
+    #[test]
+    fn filters_by_level() {
+        let filter = LogFilter { min_level: LogLevel::Warn, ..LogFilter::default() };
+        assert!(!filter.matches(&LogEntry::parse("10-19 12:34:56.789  1  1 I Tag: info")));
+        assert!(filter.matches(&LogEntry::parse("10-19 12:34:56.789  1  1 W Tag: warn")));
+        assert!(filter.matches(&LogEntry::parse("10-19 12:34:56.789  1  1 A Tag: assert")));
+        // Lines without a level are never hidden by it
+        assert!(filter.matches(&LogEntry::parse("--------- beginning of crash")));
+    }
+
+    #[test]
+    fn filters_by_pid() {
+        let filter = LogFilter { pid: " 1234 ".to_string(), ..LogFilter::default() };
+        assert!(filter.matches(&LogEntry::parse("10-19 12:34:56.789  1234  1 I Tag: yes")));
+        assert!(!filter.matches(&LogEntry::parse("10-19 12:34:56.789  12345  1 I Tag: no")));
+        assert!(!filter.matches(&LogEntry::parse("--------- beginning of main")));
+    }
+
+    #[test]
+    fn filters_by_package_pids() {
+        let mut filter = LogFilter { package_pids: Some(vec![100, 200]), ..LogFilter::default() };
+        assert!(filter.matches(&LogEntry::parse("10-19 12:34:56.789   200  201 I Tag: yes")));
+        assert!(!filter.matches(&LogEntry::parse("10-19 12:34:56.789   300  301 I Tag: no")));
+        // A package that is not running hides everything
+        filter.package_pids = Some(Vec::new());
+        assert!(!filter.matches(&LogEntry::parse("10-19 12:34:56.789   200  201 I Tag: no")));
+    }
+
+    #[test]
+    fn filters_by_tag_and_search() {
+        let mut filter = LogFilter {
+            tag: "okhttp".to_string(),
+            search: "GET /api".to_string(),
+            ..LogFilter::default()
+        };
+        filter.compile();
+        assert!(filter.matches(&LogEntry::parse("10-19 12:34:56.789  1  1 D OkHttp: --> GET /api/users")));
+        assert!(!filter.matches(&LogEntry::parse("10-19 12:34:56.789  1  1 D OkHttp: --> POST /api/users")));
+        assert!(!filter.matches(&LogEntry::parse("10-19 12:34:56.789  1  1 D Retrofit: --> GET /api/users")));
+    }
+}
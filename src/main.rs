@@ -1,8 +1,24 @@
 use eframe::egui;
 use egui::ViewportBuilder;
 mod adb;
+mod apk_install;
+mod crash_monitor;
+mod device_context;
+mod device_labels;
+mod device_tracker;
+mod logcat;
+mod port_rules;
+mod shell_session;
+mod snippets;
 mod tabs;
-use tabs::{ConnectTab, DevicesTab, ReverseTcpTab, FileManagerTab};
+mod traffic_proxy;
+mod watchdog;
+use tabs::{ConnectTab, CrashesTab, DevicesTab, ReverseTcpTab, FileManagerTab, InstallTab, LogcatTab, PackagesTab, ShellTab};
+use crash_monitor::CrashMonitor;
+use snippets::SnippetLibrary;
+use device_context::DeviceContext;
+use device_labels::DeviceLabels;
+use device_tracker::DeviceTracker;
 use std::sync::Arc;
 
 struct AdbManagerApp {
@@ -10,26 +26,73 @@ struct AdbManagerApp {
     devices_tab: DevicesTab,
     reverse_tcp_tab: ReverseTcpTab,
     file_manager_tab: FileManagerTab,
+    logcat_tab: LogcatTab,
+    crashes_tab: CrashesTab,
+    shell_tab: ShellTab,
+    install_tab: InstallTab,
+    packages_tab: PackagesTab,
+    snippets: SnippetLibrary,
+    device_context: DeviceContext,
     selected_tab: usize,
     status_message: String,
 }
 
-impl Default for AdbManagerApp {
-    fn default() -> Self {
+impl AdbManagerApp {
+    fn new(cc: &eframe::CreationContext<'_>) -> Self {
         let adb_manager = Arc::new(adb::AdbManager::new());
-        Self {
-            connect_tab: ConnectTab::default(),
-            devices_tab: DevicesTab::default(),
-            reverse_tcp_tab: ReverseTcpTab::default(),
-            file_manager_tab: FileManagerTab::new(adb_manager),
+        let tracker = DeviceTracker::start(adb_manager.clone(), cc.egui_ctx.clone());
+        let crash_monitor = CrashMonitor::start(adb_manager.clone(), cc.egui_ctx.clone());
+        let labels = cc.storage.map(DeviceLabels::load).unwrap_or_default();
+
+        let mut app = Self {
+            connect_tab: ConnectTab::new(adb_manager.clone()),
+            devices_tab: DevicesTab::new(adb_manager.clone()),
+            reverse_tcp_tab: ReverseTcpTab::new(adb_manager.clone()),
+            file_manager_tab: FileManagerTab::new(adb_manager.clone()),
+            logcat_tab: LogcatTab::new(adb_manager.clone()),
+            crashes_tab: CrashesTab::new(crash_monitor),
+            shell_tab: ShellTab::new(adb_manager.clone()),
+            install_tab: InstallTab::new(adb_manager.clone()),
+            packages_tab: PackagesTab::new(adb_manager.clone()),
+            snippets: SnippetLibrary::new(adb_manager.clone()),
+            device_context: DeviceContext::new(tracker, labels),
             selected_tab: 0,
             status_message: String::new(),
+        };
+        if let Some(storage) = cc.storage {
+            app.connect_tab.load(storage);
+            app.reverse_tcp_tab.load(storage);
+            app.crashes_tab.load(storage);
+            app.snippets.load(storage);
         }
+        app
     }
 }
 
 impl eframe::App for AdbManagerApp {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.connect_tab.save(storage);
+        self.reverse_tcp_tab.save(storage);
+        self.crashes_tab.save(storage);
+        self.snippets.save(storage);
+        self.device_context.labels.save(storage);
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        // The window is gone by now, so stderr is the only place left to report to
+        for error in self.reverse_tcp_tab.stop_monitors() {
+            eprintln!("{}", error);
+        }
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.device_context.update();
+        let connected = self.device_context.take_connected();
+        self.reverse_tcp_tab.handle_connected(&connected, &self.device_context, ctx);
+        self.logcat_tab.update();
+        self.shell_tab.update();
+        self.crashes_tab.update(&self.device_context.devices);
+
         egui::TopBottomPanel::top("tabs").show(ctx, |ui| {
             ui.horizontal(|ui| {
                 if ui.selectable_label(self.selected_tab == 0, "Connect").clicked() {
@@ -37,24 +100,61 @@ impl eframe::App for AdbManagerApp {
                 }
                 if ui.selectable_label(self.selected_tab == 1, "Devices").clicked() {
                     self.selected_tab = 1;
-                    self.devices_tab.refresh_devices();
+                    self.device_context.refresh();
                 }
-                if ui.selectable_label(self.selected_tab == 2, "Reverse TCP").clicked() {
+                if ui.selectable_label(self.selected_tab == 2, "Reverse / Forward").clicked() {
                     self.selected_tab = 2;
-                    self.reverse_tcp_tab.refresh_devices();
+                    self.device_context.refresh();
                 }
                 if ui.selectable_label(self.selected_tab == 3, "File Manager").clicked() {
                     self.selected_tab = 3;
                 }
+                if ui.selectable_label(self.selected_tab == 4, "Logcat").clicked() {
+                    self.selected_tab = 4;
+                }
+                if ui.selectable_label(self.selected_tab == 5, "Crashes").clicked() {
+                    self.selected_tab = 5;
+                }
+                if ui.selectable_label(self.selected_tab == 6, "Shell").clicked() {
+                    self.selected_tab = 6;
+                }
+                if ui.selectable_label(self.selected_tab == 7, "Install").clicked() {
+                    self.selected_tab = 7;
+                }
+                if ui.selectable_label(self.selected_tab == 8, "Packages").clicked() {
+                    self.selected_tab = 8;
+                }
+
+                let unseen = self.crashes_tab.unseen_count();
+                if unseen > 0 {
+                    let text = egui::RichText::new(format!("⚠ {} new crash(es)", unseen)).color(egui::Color32::from_rgb(240, 80, 80));
+                    if ui.button(text).on_hover_text("A watched app crashed or stopped responding").clicked() {
+                        self.selected_tab = 5;
+                    }
+                }
+            });
+            ui.horizontal(|ui| {
+                self.device_context.show_selector(ui);
+                ui.separator();
+                self.snippets.show_menu(ui, &self.device_context);
             });
         });
 
+        if let Some(message) = self.snippets.show_windows(ctx, &self.device_context) {
+            self.status_message = message;
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
             if let Some(message) = match self.selected_tab {
-                0 => self.connect_tab.show(ui),
-                1 => self.devices_tab.show(ui),
-                2 => self.reverse_tcp_tab.show(ui),
-                3 => self.file_manager_tab.show(ui),
+                0 => self.connect_tab.show(ui, &mut self.device_context),
+                1 => self.devices_tab.show(ui, &mut self.device_context),
+                2 => self.reverse_tcp_tab.show(ui, &mut self.device_context),
+                3 => self.file_manager_tab.show(ui, &mut self.device_context),
+                4 => self.logcat_tab.show(ui, &mut self.device_context),
+                5 => self.crashes_tab.show(ui, &mut self.device_context),
+                6 => self.shell_tab.show(ui, &mut self.device_context),
+                7 => self.install_tab.show(ui, &mut self.device_context),
+                8 => self.packages_tab.show(ui, &mut self.device_context),
                 _ => unreachable!(),
             } {
                 self.status_message = message;
@@ -71,13 +171,13 @@ impl eframe::App for AdbManagerApp {
 fn main() -> Result<(), eframe::Error> {
     let options = eframe::NativeOptions {
         viewport: ViewportBuilder::default()
-            .with_inner_size([400.0, 300.0]),
+            .with_inner_size([900.0, 600.0]),
         ..Default::default()
     };
     
     eframe::run_native(
         "ADB Manager",
         options,
-        Box::new(|_cc| Box::new(AdbManagerApp::default())),
+        Box::new(|cc| Box::new(AdbManagerApp::new(cc))),
     )
 }
@@ -0,0 +1,310 @@
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Direction {
+    // Device connects to a socket on the host (`adb reverse`)
+    Reverse,
+    // Host connects to a socket on the device (`adb forward`)
+    Forward,
+}
+
+impl Direction {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Direction::Reverse => "reverse",
+            Direction::Forward => "forward",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SocketKind {
+    Tcp,
+    LocalAbstract,
+    LocalReserved,
+    LocalFilesystem,
+    // Debuggable process id on the device, only valid as the device end of a forward
+    Jdwp,
+}
+
+impl SocketKind {
+    pub const ALL: [SocketKind; 5] = [
+        SocketKind::Tcp,
+        SocketKind::LocalAbstract,
+        SocketKind::LocalReserved,
+        SocketKind::LocalFilesystem,
+        SocketKind::Jdwp,
+    ];
+
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            SocketKind::Tcp => "tcp",
+            SocketKind::LocalAbstract => "localabstract",
+            SocketKind::LocalReserved => "localreserved",
+            SocketKind::LocalFilesystem => "localfilesystem",
+            SocketKind::Jdwp => "jdwp",
+        }
+    }
+}
+
+// One end of a mapping in adb's "kind:value" notation, e.g. "tcp:8081" or "localabstract:foo"
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SocketSpec {
+    pub kind: SocketKind,
+    pub value: String,
+}
+
+impl SocketSpec {
+    pub fn tcp(port: u16) -> Self {
+        Self {
+            kind: SocketKind::Tcp,
+            value: port.to_string(),
+        }
+    }
+
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (prefix, value) = spec
+            .split_once(':')
+            .ok_or_else(|| format!("'{}' is missing a socket type", spec))?;
+        let kind = SocketKind::ALL
+            .into_iter()
+            .find(|k| k.prefix() == prefix)
+            .ok_or_else(|| format!("unsupported socket type '{}'", prefix))?;
+        Ok(Self {
+            kind,
+            value: value.to_string(),
+        })
+    }
+
+    // Checks the value before it reaches adb; `allow_zero` is for a host tcp:0 forward
+    pub fn validate(&self, allow_zero: bool) -> Result<(), String> {
+        match self.kind {
+            SocketKind::Tcp => match self.value.parse::<u32>() {
+                Ok(0) if allow_zero => Ok(()),
+                Ok(1..=65535) => Ok(()),
+                _ => Err(format!("'{}' is not a port between 1 and 65535", self.value)),
+            },
+            SocketKind::Jdwp => match self.value.parse::<u32>() {
+                Ok(_) => Ok(()),
+                Err(_) => Err(format!("'{}' is not a process id", self.value)),
+            },
+            _ if self.value.trim().is_empty() => Err(format!("{} needs a socket name", self.kind.prefix())),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        match self.kind {
+            SocketKind::Tcp => self.value.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SocketSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.kind.prefix(), self.value)
+    }
+}
+
+// A mapping between a socket on the device and one on the host
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PortRule {
+    pub direction: Direction,
+    pub device: SocketSpec,
+    pub host: SocketSpec,
+    pub no_rebind: bool,
+}
+
+impl PortRule {
+    pub fn tcp(direction: Direction, device_port: u16, host_port: u16) -> Self {
+        Self {
+            direction,
+            device: SocketSpec::tcp(device_port),
+            host: SocketSpec::tcp(host_port),
+            no_rebind: false,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.host.kind == SocketKind::Jdwp || (self.direction == Direction::Reverse && self.device.kind == SocketKind::Jdwp) {
+            return Err("jdwp is only valid as the device end of a forward".to_string());
+        }
+        self.device.validate(false).map_err(|e| format!("device socket: {}", e))?;
+        self.host
+            .validate(self.direction == Direction::Forward)
+            .map_err(|e| format!("host socket: {}", e))
+    }
+
+    // The end adb listens on; a second rule with the same listener replaces the first
+    pub fn listener(&self) -> &SocketSpec {
+        match self.direction {
+            Direction::Reverse => &self.device,
+            Direction::Forward => &self.host,
+        }
+    }
+
+    // `adb reverse` takes the device socket first, `adb forward` the host socket
+    pub fn args(&self) -> Vec<String> {
+        let mut args = vec![self.direction.label().to_string()];
+        if self.no_rebind {
+            args.push("--no-rebind".to_string());
+        }
+        match self.direction {
+            Direction::Reverse => {
+                args.push(self.device.to_string());
+                args.push(self.host.to_string());
+            }
+            Direction::Forward => {
+                args.push(self.host.to_string());
+                args.push(self.device.to_string());
+            }
+        }
+        args
+    }
+}
+
+impl fmt::Display for PortRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.direction {
+            Direction::Reverse => write!(f, "reverse device {} → host {}", self.device, self.host),
+            Direction::Forward => write!(f, "forward host {} → device {}", self.host, self.device),
+        }
+    }
+}
+
+// A named bundle of rules, e.g. everything a React Native project needs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortPreset {
+    pub name: String,
+    pub rules: Vec<PortRule>,
+    // Apply to every device as soon as it connects
+    pub auto_apply: bool,
+}
+
+impl PortPreset {
+    fn reverse(name: &str, ports: &[u16]) -> Self {
+        Self {
+            name: name.to_string(),
+            rules: ports.iter().map(|&p| PortRule::tcp(Direction::Reverse, p, p)).collect(),
+            auto_apply: false,
+        }
+    }
+
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self::reverse("React Native (Metro)", &[8081]),
+            Self::reverse("Flipper", &[8088, 8089]),
+            Self::reverse("Webpack dev server", &[3000]),
+            Self::reverse("Mock backend", &[8080]),
+        ]
+    }
+}
+
+// Whether anything accepts connections on the host port a reverse rule points at
+pub fn is_host_port_listening(port: u16) -> bool {
+    let address = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    TcpStream::connect_timeout(&address, Duration::from_millis(200)).is_ok()
+}
+
+pub fn socket_spec_editor(ui: &mut egui::Ui, id_source: impl std::hash::Hash, spec: &mut SocketSpec) {
+    egui::ComboBox::from_id_source(id_source)
+        .width(120.0)
+        .selected_text(spec.kind.prefix())
+        .show_ui(ui, |ui| {
+            for kind in SocketKind::ALL {
+                ui.selectable_value(&mut spec.kind, kind, kind.prefix());
+            }
+        });
+    ui.add(egui::TextEdit::singleline(&mut spec.value).desired_width(140.0));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_socket_specs_round_trip() {
+        for spec in [
+            "localabstract:chrome_devtools_remote",
+            "localreserved:debuggerd",
+            "localfilesystem:/data/local/tmp/socket",
+            "tcp:8081",
+            "jdwp:1234",
+        ] {
+            let parsed = SocketSpec::parse(spec).unwrap();
+            assert_eq!(parsed.to_string(), spec);
+        }
+        assert_eq!(SocketSpec::parse("localabstract:foo").unwrap().kind, SocketKind::LocalAbstract);
+        assert_eq!(SocketSpec::parse("localreserved:foo").unwrap().kind, SocketKind::LocalReserved);
+        assert_eq!(SocketSpec::parse("localfilesystem:foo").unwrap().kind, SocketKind::LocalFilesystem);
+    }
+
+    #[test]
+    fn socket_names_keep_their_colons() {
+        let spec = SocketSpec::parse("localabstract:webview_devtools_remote:1").unwrap();
+        assert_eq!(spec.value, "webview_devtools_remote:1");
+        assert_eq!(spec.to_string(), "localabstract:webview_devtools_remote:1");
+    }
+
+    #[test]
+    fn rejects_unknown_socket_types() {
+        assert!(SocketSpec::parse("8081").is_err());
+        assert!(SocketSpec::parse("udp:8081").is_err());
+    }
+
+    #[test]
+    fn reverse_args_put_the_device_socket_first() {
+        let rule = PortRule::tcp(Direction::Reverse, 8081, 9081);
+        assert_eq!(rule.args(), ["reverse", "tcp:8081", "tcp:9081"]);
+    }
+
+    #[test]
+    fn forward_args_put_the_host_socket_first() {
+        let mut rule = PortRule::tcp(Direction::Forward, 9222, 0);
+        rule.device = SocketSpec::parse("localabstract:chrome_devtools_remote").unwrap();
+        rule.no_rebind = true;
+        assert_eq!(rule.args(), ["forward", "--no-rebind", "tcp:0", "localabstract:chrome_devtools_remote"]);
+    }
+
+    #[test]
+    fn tcp_ports_must_be_in_range() {
+        assert!(SocketSpec::tcp(1).validate(false).is_ok());
+        assert!(SocketSpec::tcp(65535).validate(false).is_ok());
+        assert!(SocketSpec::tcp(0).validate(false).is_err());
+        assert!(SocketSpec::parse("tcp:65536").unwrap().validate(true).is_err());
+        assert!(SocketSpec::parse("tcp:-1").unwrap().validate(false).is_err());
+        assert!(SocketSpec::parse("tcp:http").unwrap().validate(false).is_err());
+        assert!(SocketSpec::parse("localabstract: ").unwrap().validate(false).is_err());
+    }
+
+    #[test]
+    fn tcp_zero_only_on_the_host_end_of_a_forward() {
+        assert!(PortRule::tcp(Direction::Forward, 9222, 0).validate().is_ok());
+        assert!(PortRule::tcp(Direction::Reverse, 8081, 0).validate().is_err());
+        assert!(PortRule::tcp(Direction::Forward, 0, 9222).validate().is_err());
+        assert!(PortRule::tcp(Direction::Reverse, 0, 8081).validate().is_err());
+    }
+
+    #[test]
+    fn jdwp_only_on_the_device_end_of_a_forward() {
+        let mut forward = PortRule::tcp(Direction::Forward, 0, 8700);
+        forward.device = SocketSpec::parse("jdwp:4321").unwrap();
+        assert!(forward.validate().is_ok());
+
+        let mut reverse = forward.clone();
+        reverse.direction = Direction::Reverse;
+        assert!(reverse.validate().is_err());
+
+        let mut host_jdwp = PortRule::tcp(Direction::Forward, 8700, 8700);
+        host_jdwp.host = SocketSpec::parse("jdwp:4321").unwrap();
+        assert!(host_jdwp.validate().is_err());
+
+        forward.device = SocketSpec::parse("jdwp:app").unwrap();
+        assert!(forward.validate().is_err());
+    }
+}
@@ -0,0 +1,176 @@
+use eframe::egui;
+use regex::Regex;
+use std::io::{Read, Write};
+use std::process::{Child, ChildStdin};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::OnceLock;
+use std::thread;
+use crate::adb::AdbManager;
+
+// Ctrl-C as the device's terminal expects it
+const INTERRUPT: &str = "\x03";
+// An unfinished escape sequence longer than this is passed through rather than held back
+const MAX_HELD_ESCAPE: usize = 256;
+
+// A persistent `adb shell` with a PTY on the device, so prompts, job control and Ctrl-C behave
+// like in a terminal. Output arrives in chunks rather than lines because prompts end without one.
+pub struct ShellSession {
+    child: Child,
+    stdin: ChildStdin,
+    output: Receiver<String>,
+}
+
+impl ShellSession {
+    pub fn start(adb_manager: &AdbManager, device: &str, ctx: egui::Context) -> Result<Self, String> {
+        // -tt forces a PTY even though our stdin is a pipe
+        let mut child = adb_manager.spawn_adb_interactive(&["-s", device, "shell", "-tt"])?;
+        let stdin = child.stdin.take().ok_or("shell has no input")?;
+
+        let (sender, output) = mpsc::channel();
+        if let Some(stdout) = child.stdout.take() {
+            read_chunks(stdout, sender.clone(), ctx.clone());
+        }
+        if let Some(stderr) = child.stderr.take() {
+            read_chunks(stderr, sender, ctx);
+        }
+
+        Ok(Self { child, stdin, output })
+    }
+
+    pub fn send(&mut self, text: &str) -> Result<(), String> {
+        self.stdin
+            .write_all(text.as_bytes())
+            .and_then(|_| self.stdin.flush())
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn interrupt(&mut self) -> Result<(), String> {
+        self.send(INTERRUPT)
+    }
+
+    pub fn receive(&self) -> String {
+        self.output.try_iter().collect()
+    }
+
+    // Some(message) once the shell has exited
+    pub fn exit_message(&mut self) -> Option<String> {
+        let status = self.child.try_wait().ok()??;
+        Some(format!("Session ended ({})", status))
+    }
+}
+
+impl Drop for ShellSession {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn read_chunks<R: Read + Send + 'static>(mut reader: R, sender: Sender<String>, ctx: egui::Context) {
+    thread::spawn(move || {
+        let mut buffer = [0u8; 4096];
+        // Bytes of a UTF-8 character split across two reads
+        let mut pending = Vec::new();
+        // The start of an escape sequence whose end has not been read yet
+        let mut held = String::new();
+        loop {
+            let n = match reader.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n,
+            };
+            pending.extend_from_slice(&buffer[..n]);
+            let valid = match std::str::from_utf8(&pending) {
+                Ok(_) => pending.len(),
+                Err(e) if e.error_len().is_none() => e.valid_up_to(),
+                Err(_) => pending.len(),
+            };
+            let mut text = std::mem::take(&mut held);
+            text.push_str(&String::from_utf8_lossy(&pending[..valid]));
+            pending.drain(..valid);
+            held = text.split_off(incomplete_escape_start(&text));
+            if sender.send(strip_ansi(&text)).is_err() {
+                break;
+            }
+            ctx.request_repaint();
+        }
+        if !held.is_empty() {
+            let _ = sender.send(strip_ansi(&held));
+        }
+        ctx.request_repaint();
+    });
+}
+
+// Where a trailing escape sequence that is cut off by the end of the read starts, or the
+// length of `text` if it ends with none
+fn incomplete_escape_start(text: &str) -> usize {
+    static INCOMPLETE: OnceLock<Regex> = OnceLock::new();
+    let incomplete = INCOMPLETE.get_or_init(|| Regex::new(r"^\x1b(?:\[[0-?]*[ -/]*|\][^\x07\x1b]*\x1b?)?$").unwrap());
+    // The last two escapes, as an OSC sequence can be cut inside its ESC \ terminator
+    text.rmatch_indices('\x1b')
+        .take(2)
+        .map(|(start, _)| start)
+        .filter(|&start| text.len() - start <= MAX_HELD_ESCAPE && incomplete.is_match(&text[start..]))
+        .last()
+        .unwrap_or(text.len())
+}
+
+// Removes colors, cursor movement and title sequences, and the \r of the PTY's \r\n line endings
+pub fn strip_ansi(text: &str) -> String {
+    static ANSI: OnceLock<Regex> = OnceLock::new();
+    let ansi = ANSI.get_or_init(|| {
+        Regex::new(r"\x1b\[[0-?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(?:\x07|\x1b\\)|\x1b[@-Z\\-_]|[\r\x07]").unwrap()
+    });
+    ansi.replace_all(text, "").into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // What read_chunks sends for each read, holding back cut-off escapes the same way
+    fn stream(reads: &[&str]) -> Vec<String> {
+        let mut held = String::new();
+        reads
+            .iter()
+            .map(|read| {
+                let mut text = std::mem::take(&mut held);
+                text.push_str(read);
+                held = text.split_off(incomplete_escape_start(&text));
+                strip_ansi(&text)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn strips_colors_titles_and_carriage_returns() {
+        assert_eq!(strip_ansi("\x1b[1;32mok\x1b[0m\r\n"), "ok\n");
+        assert_eq!(strip_ansi("\x1b]0;shell@pixel\x07$ "), "$ ");
+        assert_eq!(strip_ansi("\x1b]2;title\x1b\\$ "), "$ ");
+    }
+
+    #[test]
+    fn holds_back_a_csi_sequence_split_in_the_middle() {
+        assert_eq!(incomplete_escape_start("ls\x1b[0;3"), 2);
+        assert_eq!(stream(&["ls\x1b[0;3", "4mdir\x1b[0m\r\n"]), ["ls", "dir\n"]);
+    }
+
+    #[test]
+    fn holds_back_an_osc_sequence_split_inside_its_terminator() {
+        assert_eq!(incomplete_escape_start("a\x1b]0;pixel:/ $\x1b"), 1);
+        assert_eq!(stream(&["a\x1b]0;pixel:/ $\x1b", "\\b"]), ["a", "b"]);
+    }
+
+    #[test]
+    fn holds_back_a_bare_escape_at_the_end() {
+        assert_eq!(incomplete_escape_start("prompt\x1b"), 6);
+        assert_eq!(stream(&["prompt\x1b", "[Kdone"]), ["prompt", "done"]);
+    }
+
+    #[test]
+    fn passes_complete_and_plain_text_through() {
+        assert_eq!(incomplete_escape_start("plain text"), 10);
+        assert_eq!(incomplete_escape_start("\x1b[31mred\x1b[0m"), 12);
+        let long = format!("\x1b]0;{}", "x".repeat(MAX_HELD_ESCAPE));
+        assert_eq!(incomplete_escape_start(&long), long.len());
+    }
+}
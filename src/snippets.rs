@@ -0,0 +1,330 @@
+use eframe::egui;
+use rfd::FileDialog;
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use crate::adb::{AdbManager, ShellOutput};
+use crate::device_context::DeviceContext;
+
+const SETTINGS_KEY: &str = "snippets";
+const SNIPPET_TIMEOUT: Duration = Duration::from_secs(60);
+
+// A named shell command; `{package}` and `{device}` are filled in when it runs
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snippet {
+    pub name: String,
+    pub command: String,
+}
+
+impl Snippet {
+    fn new(name: &str, command: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            command: command.to_string(),
+        }
+    }
+
+    fn defaults() -> Vec<Self> {
+        vec![
+            Self::new("Clear app data", "pm clear {package}"),
+            Self::new("Force stop app", "am force-stop {package}"),
+            Self::new("Launch app", "monkey -p {package} -c android.intent.category.LAUNCHER 1"),
+            Self::new("Show taps", "settings put system show_touches 1"),
+            Self::new("Hide taps", "settings put system show_touches 0"),
+            Self::new("Stay awake while charging", "svc power stayon true"),
+        ]
+    }
+
+    pub fn uses_package(&self) -> bool {
+        self.command.contains("{package}")
+    }
+
+    pub fn render(&self, device: &str, package: &str) -> Result<String, String> {
+        if self.uses_package() && package.trim().is_empty() {
+            return Err(format!("\"{}\" needs a package name", self.name));
+        }
+        Ok(self.command.replace("{package}", package.trim()).replace("{device}", device))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+struct SnippetSettings {
+    snippets: Vec<Snippet>,
+    package: String,
+}
+
+impl Default for SnippetSettings {
+    fn default() -> Self {
+        Self {
+            snippets: Snippet::defaults(),
+            package: String::new(),
+        }
+    }
+}
+
+struct SnippetRun {
+    name: String,
+    device_id: String,
+    command: String,
+    result: Option<Result<ShellOutput, String>>,
+    receiver: Receiver<Result<ShellOutput, String>>,
+}
+
+// The "Snippets" menu in the top panel, with its editor and output windows
+pub struct SnippetLibrary {
+    adb_manager: Arc<AdbManager>,
+    snippets: Vec<Snippet>,
+    // Value for {package}, shared by all snippets
+    package: String,
+    editor_open: bool,
+    run: Option<SnippetRun>,
+    output_open: bool,
+    message: Option<String>,
+}
+
+impl SnippetLibrary {
+    pub fn new(adb_manager: Arc<AdbManager>) -> Self {
+        let settings = SnippetSettings::default();
+        Self {
+            adb_manager,
+            snippets: settings.snippets,
+            package: settings.package,
+            editor_open: false,
+            run: None,
+            output_open: false,
+            message: None,
+        }
+    }
+
+    pub fn load(&mut self, storage: &dyn eframe::Storage) {
+        if let Some(settings) = eframe::get_value::<SnippetSettings>(storage, SETTINGS_KEY) {
+            self.snippets = settings.snippets;
+            self.package = settings.package;
+        }
+    }
+
+    pub fn save(&self, storage: &mut dyn eframe::Storage) {
+        let settings = SnippetSettings {
+            snippets: self.snippets.clone(),
+            package: self.package.clone(),
+        };
+        eframe::set_value(storage, SETTINGS_KEY, &settings);
+    }
+
+    fn start(&mut self, snippet: &Snippet, device_id: &str, ctx: &egui::Context) {
+        let command = match snippet.render(device_id, &self.package) {
+            Ok(command) => command,
+            Err(e) => {
+                self.message = Some(format!("Error: {}", e));
+                return;
+            }
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        let adb_manager = self.adb_manager.clone();
+        let thread_device = device_id.to_string();
+        let thread_command = command.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let _ = sender.send(adb_manager.run_shell(&thread_device, &thread_command, SNIPPET_TIMEOUT));
+            ctx.request_repaint();
+        });
+
+        self.run = Some(SnippetRun {
+            name: snippet.name.clone(),
+            device_id: device_id.to_string(),
+            command,
+            result: None,
+            receiver,
+        });
+        self.output_open = true;
+    }
+
+    fn import(&mut self) -> Option<String> {
+        let path = FileDialog::new()
+            .set_title("Import snippets")
+            .add_filter("JSON", &["json"])
+            .pick_file()?;
+        let imported: Vec<Snippet> = match std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| serde_json::from_str(&content).map_err(|e| e.to_string()))
+        {
+            Ok(snippets) => snippets,
+            Err(e) => return Some(format!("Error: importing {}: {}", path.display(), e)),
+        };
+
+        // Snippets with a name that already exists replace the existing one
+        let count = imported.len();
+        for snippet in imported {
+            match self.snippets.iter_mut().find(|s| s.name == snippet.name) {
+                Some(existing) => *existing = snippet,
+                None => self.snippets.push(snippet),
+            }
+        }
+        Some(format!("Imported {} snippet(s) from {}", count, path.display()))
+    }
+
+    fn export(&self) -> Option<String> {
+        let path = FileDialog::new()
+            .set_title("Export snippets")
+            .set_file_name("snippets.json")
+            .add_filter("JSON", &["json"])
+            .save_file()?;
+        let result = serde_json::to_string_pretty(&self.snippets)
+            .map_err(|e| e.to_string())
+            .and_then(|json| std::fs::write(&path, json).map_err(|e| e.to_string()));
+        Some(match result {
+            Ok(_) => format!("Exported {} snippet(s) to {}", self.snippets.len(), path.display()),
+            Err(e) => format!("Error: exporting to {}: {}", path.display(), e),
+        })
+    }
+
+    pub fn show_menu(&mut self, ui: &mut egui::Ui, context: &DeviceContext) {
+        ui.menu_button("Snippets", |ui| {
+            ui.horizontal(|ui| {
+                ui.label("{package}:");
+                ui.add(egui::TextEdit::singleline(&mut self.package).desired_width(180.0).hint_text("com.example.app"));
+            });
+            ui.separator();
+
+            let device = context.active_device().filter(|d| d.is_usable()).cloned();
+            if device.is_none() {
+                ui.weak("Pick a connected device to run snippets");
+            }
+            let mut picked = None;
+            for snippet in &self.snippets {
+                let response = ui
+                    .add_enabled(device.is_some(), egui::Button::new(&snippet.name))
+                    .on_hover_text(&snippet.command);
+                if response.clicked() {
+                    picked = Some(snippet.clone());
+                }
+            }
+            if let (Some(snippet), Some(device)) = (picked, &device) {
+                self.start(&snippet, &device.id, ui.ctx());
+                ui.close_menu();
+            }
+
+            ui.separator();
+            if ui.button("Manage snippets…").clicked() {
+                self.editor_open = true;
+                ui.close_menu();
+            }
+        });
+    }
+
+    // Returns a status message for the main window, e.g. after an import
+    pub fn show_windows(&mut self, ctx: &egui::Context, context: &DeviceContext) -> Option<String> {
+        self.show_editor(ctx);
+        self.show_output(ctx, context);
+        self.message.take()
+    }
+
+    fn show_editor(&mut self, ctx: &egui::Context) {
+        let mut open = self.editor_open;
+        egui::Window::new("Snippets").open(&mut open).default_width(520.0).show(ctx, |ui| {
+            ui.label("Shell commands run on the active device. {package} and {device} are replaced when a snippet runs.");
+            ui.separator();
+
+            let mut removed = None;
+            egui::ScrollArea::vertical().max_height(320.0).show(ui, |ui| {
+                egui::Grid::new("snippet_editor").num_columns(3).striped(true).show(ui, |ui| {
+                    ui.strong("Name");
+                    ui.strong("Command");
+                    ui.label("");
+                    ui.end_row();
+                    for (index, snippet) in self.snippets.iter_mut().enumerate() {
+                        ui.add(egui::TextEdit::singleline(&mut snippet.name).desired_width(140.0));
+                        ui.add(egui::TextEdit::singleline(&mut snippet.command).font(egui::TextStyle::Monospace).desired_width(300.0));
+                        if ui.small_button("✖").on_hover_text("Delete snippet").clicked() {
+                            removed = Some(index);
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+            if let Some(index) = removed {
+                self.snippets.remove(index);
+            }
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("Add snippet").clicked() {
+                    self.snippets.push(Snippet::new("New snippet", "am start -n {package}/.MainActivity"));
+                }
+                if ui.button("Import…").on_hover_text("Add snippets from a JSON file shared by the team").clicked() {
+                    self.message = self.import();
+                }
+                if ui.button("Export…").clicked() {
+                    self.message = self.export();
+                }
+            });
+        });
+        self.editor_open = open;
+    }
+
+    fn show_output(&mut self, ctx: &egui::Context, context: &DeviceContext) {
+        let Some(run) = &mut self.run else {
+            return;
+        };
+        if let Ok(result) = run.receiver.try_recv() {
+            run.result = Some(result);
+        }
+
+        let mut open = self.output_open;
+        egui::Window::new("Snippet output").open(&mut open).default_width(480.0).show(ctx, |ui| {
+            ui.label(format!("{} on {}", run.name, context.labels.display_name(&run.device_id)));
+            ui.monospace(format!("$ {}", run.command));
+            ui.separator();
+            match &run.result {
+                None => {
+                    ui.spinner();
+                }
+                Some(Err(e)) => {
+                    ui.colored_label(egui::Color32::RED, format!("Failed: {}", e.trim()));
+                }
+                Some(Ok(output)) => {
+                    match output.exit_code {
+                        Some(0) => ui.colored_label(egui::Color32::GREEN, "exit 0"),
+                        Some(code) => ui.colored_label(egui::Color32::RED, format!("exit {}", code)),
+                        None => ui.colored_label(egui::Color32::RED, "killed"),
+                    };
+                    let text = format!("{}{}", output.stdout, output.stderr);
+                    egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                        ui.add(egui::TextEdit::multiline(&mut text.as_str()).font(egui::TextStyle::Monospace).desired_width(f32::INFINITY));
+                    });
+                }
+            }
+        });
+        self.output_open = open;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_in_package_and_device() {
+        let snippet = Snippet::new("Launch", "monkey -p {package} 1 # {device} {package}");
+        assert_eq!(
+            snippet.render("emulator-5554", " com.example.app "),
+            Ok("monkey -p com.example.app 1 # emulator-5554 com.example.app".to_string())
+        );
+    }
+
+    #[test]
+    fn leaves_commands_without_placeholders_alone() {
+        let snippet = Snippet::new("Show taps", "settings put system show_touches 1");
+        assert_eq!(snippet.render("emulator-5554", ""), Ok("settings put system show_touches 1".to_string()));
+    }
+
+    #[test]
+    fn missing_package_is_an_error() {
+        let snippet = Snippet::new("Clear app data", "pm clear {package}");
+        assert_eq!(snippet.render("emulator-5554", "  "), Err("\"Clear app data\" needs a package name".to_string()));
+    }
+}
@@ -1,49 +1,610 @@
 use eframe::egui;
-use std::process::Command;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use qrcode::{Color, QrCode};
+use serde::{Deserialize, Serialize};
+use crate::adb::{AdbManager, ConnectResult, MdnsService};
+use crate::device_context::DeviceContext;
+use crate::watchdog::Watchdog;
+
+const QR_PAIRING_TIMEOUT: Duration = Duration::from_secs(120);
+const DISCOVERY_INTERVAL: Duration = Duration::from_secs(3);
+const DEFAULT_ADB_PORT: u16 = 5555;
+const SETTINGS_KEY: &str = "connect_tab";
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct ConnectSettings {
+    saved_targets: Vec<String>,
+    watchdog_enabled: bool,
+}
+
+struct QrPairing {
+    service_name: String,
+    password: String,
+    modules: Vec<bool>,
+    width: usize,
+    result: Receiver<Result<String, String>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+struct Connecting {
+    address: String,
+    result: Receiver<ConnectResult>,
+}
+
+impl Drop for QrPairing {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
 
 pub struct ConnectTab {
+    adb_manager: Arc<AdbManager>,
     ip: String,
     port: String,
+    pairing_port: String,
+    pairing_code: String,
+    qr_pairing: Option<QrPairing>,
+    connecting: Option<Connecting>,
+    pairing: Option<Receiver<Result<String, String>>>,
+    discovered: Vec<MdnsService>,
+    discovery_error: Option<String>,
+    last_discovery: Option<Instant>,
+    discovery: Option<Receiver<Result<Vec<MdnsService>, String>>>,
+    saved_targets: Vec<String>,
+    watchdog_enabled: bool,
+    watchdog: Watchdog,
 }
 
 impl Default for ConnectTab {
     fn default() -> Self {
+        Self::new(Arc::new(AdbManager::new()))
+    }
+}
+
+impl ConnectTab {
+    pub fn new(adb_manager: Arc<AdbManager>) -> Self {
         Self {
+            watchdog: Watchdog::new(adb_manager.clone()),
+            adb_manager,
             ip: String::from("192.168.1.105"),
             port: String::new(),
+            pairing_port: String::new(),
+            pairing_code: String::new(),
+            qr_pairing: None,
+            connecting: None,
+            pairing: None,
+            discovered: Vec::new(),
+            discovery_error: None,
+            last_discovery: None,
+            discovery: None,
+            saved_targets: Vec::new(),
+            watchdog_enabled: false,
         }
     }
-}
 
-impl ConnectTab {
-    pub fn show(&mut self, ui: &mut egui::Ui) -> Option<String> {
-        ui.heading("ADB Connection Manager");
-        
-        ui.horizontal(|ui| {
-            ui.label("IP Address:");
-            ui.text_edit_singleline(&mut self.ip);
+    pub fn load(&mut self, storage: &dyn eframe::Storage) {
+        if let Some(settings) = eframe::get_value::<ConnectSettings>(storage, SETTINGS_KEY) {
+            self.saved_targets = settings.saved_targets;
+            self.watchdog_enabled = settings.watchdog_enabled;
+            self.watchdog.set_targets(&self.saved_targets);
+            self.watchdog.set_enabled(self.watchdog_enabled);
+        }
+    }
+
+    pub fn save(&self, storage: &mut dyn eframe::Storage) {
+        let settings = ConnectSettings {
+            saved_targets: self.saved_targets.clone(),
+            watchdog_enabled: self.watchdog_enabled,
+        };
+        eframe::set_value(storage, SETTINGS_KEY, &settings);
+    }
+
+    fn save_target(&mut self) -> String {
+        match connect_address(&self.ip, &self.port) {
+            Ok(address) if self.saved_targets.contains(&address) => format!("{} is already saved", address),
+            Ok(address) => {
+                self.saved_targets.push(address.clone());
+                self.watchdog.set_targets(&self.saved_targets);
+                format!("Saved {}", address)
+            }
+            Err(e) => format!("Invalid address: {}", e),
+        }
+    }
+
+    // Queries adb on a worker thread; `receive_discovered` picks up the result
+    fn refresh_discovered(&mut self, ctx: &egui::Context) {
+        if self.discovery.is_some() {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        let adb_manager = self.adb_manager.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let _ = sender.send(adb_manager.get_mdns_services());
+            ctx.request_repaint();
+        });
+        self.discovery = Some(receiver);
+        self.last_discovery = Some(Instant::now());
+    }
+
+    fn receive_discovered(&mut self) {
+        let Some(result) = self.discovery.as_ref().and_then(|r| r.try_recv().ok()) else {
+            return;
+        };
+        self.discovery = None;
+        match result {
+            Ok(services) => {
+                self.discovered = services
+                    .into_iter()
+                    .filter(|s| s.is_connect() || s.is_pairing())
+                    .collect();
+                self.discovery_error = None;
+            }
+            Err(e) => {
+                self.discovered.clear();
+                self.discovery_error = Some(e.trim().to_string());
+            }
+        }
+    }
+
+    // Connecting can take up to CONNECT_TIMEOUT, so it runs on a worker thread
+    fn connect_to(&mut self, address: &str, ctx: &egui::Context) {
+        if self.connecting.is_some() {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        let adb_manager = self.adb_manager.clone();
+        let thread_address = address.to_string();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let _ = sender.send(adb_manager.connect(&thread_address));
+            ctx.request_repaint();
+        });
+        self.connecting = Some(Connecting {
+            address: address.to_string(),
+            result: receiver,
         });
-        
-        ui.horizontal(|ui| {
-            ui.label("Port:");
-            ui.text_edit_singleline(&mut self.port);
+    }
+
+    // A freshly connected device becomes the active one
+    fn receive_connected(&mut self, context: &mut DeviceContext) -> Option<String> {
+        let result = self.connecting.as_ref()?.result.try_recv().ok()?;
+        let connecting = self.connecting.take()?;
+        if result.is_connected() {
+            context.refresh();
+            context.set_active(&connecting.address);
+        }
+        Some(result.to_string())
+    }
+
+    // Pairs on a worker thread; returns a message only when the form is invalid
+    fn pair_with_code(&mut self, ctx: &egui::Context) -> Option<String> {
+        if self.pairing_code.len() != 6 || !self.pairing_code.chars().all(|c| c.is_ascii_digit()) {
+            return Some("Pairing code must be six digits".to_string());
+        }
+        if self.pairing_port.trim().is_empty() {
+            return Some("Enter the pairing port shown on the device".to_string());
+        }
+
+        let address = match connect_address(&self.ip, &self.pairing_port) {
+            Ok(address) => address,
+            Err(e) => return Some(format!("Invalid address: {}", e)),
+        };
+        let (sender, receiver) = mpsc::channel();
+        let adb_manager = self.adb_manager.clone();
+        let code = self.pairing_code.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let _ = sender.send(adb_manager.pair(&address, &code));
+            ctx.request_repaint();
         });
-        
-        let mut message = None;
-        if ui.button("Connect").clicked() {
-            let address = format!("{}:{}", self.ip, self.port);
-            match Command::new("adb")
-                .args(["connect", &address])
-                .output() 
-            {
-                Ok(output) => {
-                    message = Some(String::from_utf8_lossy(&output.stdout).to_string());
-                }
-                Err(e) => {
-                    message = Some(format!("Error: {}", e));
+        self.pairing = Some(receiver);
+        None
+    }
+
+    fn receive_paired(&mut self) -> Option<String> {
+        let result = self.pairing.as_ref()?.try_recv().ok()?;
+        self.pairing = None;
+        Some(match result {
+            Ok(output) => output,
+            Err(e) => format!("Pairing failed: {}", e.trim()),
+        })
+    }
+
+    fn start_qr_pairing(&mut self, ctx: &egui::Context) -> Result<(), String> {
+        let service_name = format!("studio-{}", random_token(10)?);
+        let password = random_token(12)?;
+        let payload = format!("WIFI:T:ADB;S:{};P:{};;", service_name, password);
+        let code = QrCode::new(payload.as_bytes()).map_err(|e| e.to_string())?;
+
+        let (sender, receiver) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let adb_manager = self.adb_manager.clone();
+        let thread_cancelled = cancelled.clone();
+        let thread_service = service_name.clone();
+        let thread_password = password.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let started = Instant::now();
+            // The device advertises the service name from the QR code once it has been scanned
+            while !thread_cancelled.load(Ordering::Relaxed) {
+                if started.elapsed() > QR_PAIRING_TIMEOUT {
+                    let _ = sender.send(Err("Timed out waiting for the device to scan the code".to_string()));
+                    break;
+                }
+
+                let service = adb_manager
+                    .get_mdns_services()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .find(|s| s.is_pairing() && s.name == thread_service);
+                if let Some(service) = service {
+                    let _ = sender.send(adb_manager.pair(&service.address, &thread_password));
+                    break;
                 }
+
+                thread::sleep(Duration::from_secs(1));
             }
-        }
+            ctx.request_repaint();
+        });
+
+        self.qr_pairing = Some(QrPairing {
+            service_name,
+            password,
+            modules: code.to_colors().into_iter().map(|c| c == Color::Dark).collect(),
+            width: code.width(),
+            result: receiver,
+            cancelled,
+        });
+        Ok(())
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, context: &mut DeviceContext) -> Option<String> {
+        let mut message = self.receive_connected(context).or_else(|| self.receive_paired());
+        let can_connect = self.connecting.is_none();
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.heading("ADB Connection Manager");
+
+            ui.horizontal(|ui| {
+                ui.label("IP Address:");
+                ui.text_edit_singleline(&mut self.ip);
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Port:");
+                ui.add(egui::TextEdit::singleline(&mut self.port).hint_text(DEFAULT_ADB_PORT.to_string()));
+            });
+
+            ui.horizontal(|ui| {
+                if ui.add_enabled(can_connect, egui::Button::new("Connect")).clicked() {
+                    match connect_address(&self.ip, &self.port) {
+                        Ok(address) => self.connect_to(&address, ui.ctx()),
+                        Err(e) => message = Some(format!("Invalid address: {}", e)),
+                    }
+                }
+                if ui.button("Save target").clicked() {
+                    message = Some(self.save_target());
+                }
+                if let Some(connecting) = &self.connecting {
+                    ui.spinner();
+                    ui.label(format!("Connecting to {}…", connecting.address));
+                }
+            });
+
+            ui.separator();
+            ui.collapsing("Saved targets & auto-reconnect", |ui| {
+                if ui.checkbox(&mut self.watchdog_enabled, "Reconnect missing or offline targets automatically").changed() {
+                    self.watchdog.set_enabled(self.watchdog_enabled);
+                }
+
+                if self.saved_targets.is_empty() {
+                    ui.label("No saved targets");
+                }
+                let mut removed = None;
+                let mut connect = None;
+                for (index, target) in self.saved_targets.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label(target);
+                        if ui.add_enabled(can_connect, egui::Button::new("Connect")).clicked() {
+                            connect = Some(target.clone());
+                        }
+                        if ui.button("Remove").clicked() {
+                            removed = Some(index);
+                        }
+                    });
+                }
+                if let Some(target) = connect {
+                    self.connect_to(&target, ui.ctx());
+                }
+                if let Some(index) = removed {
+                    self.saved_targets.remove(index);
+                    self.watchdog.set_targets(&self.saved_targets);
+                }
+
+                let history = self.watchdog.history();
+                if !history.is_empty() {
+                    ui.horizontal(|ui| {
+                        ui.label("Reconnect history:");
+                        if ui.small_button("Clear").clicked() {
+                            self.watchdog.clear_history();
+                        }
+                    });
+                    egui::ScrollArea::vertical()
+                        .id_source("watchdog_history")
+                        .max_height(150.0)
+                        .stick_to_bottom(true)
+                        .show(ui, |ui| {
+                            for event in &history {
+                                ui.label(format!("[{}] {}: {}", event.time, event.target, event.message));
+                            }
+                        });
+                }
+                if self.watchdog_enabled {
+                    ui.ctx().request_repaint_after(Duration::from_secs(1));
+                }
+            });
+
+            ui.separator();
+            ui.collapsing("Pair with pairing code (Android 11+)", |ui| {
+                ui.label("Developer options → Wireless debugging → Pair device with pairing code");
+                ui.horizontal(|ui| {
+                    ui.label("Pairing port:");
+                    ui.text_edit_singleline(&mut self.pairing_port);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Pairing code:");
+                    ui.add(egui::TextEdit::singleline(&mut self.pairing_code).char_limit(6));
+                });
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(self.pairing.is_none(), egui::Button::new("Pair")).clicked() {
+                        message = self.pair_with_code(ui.ctx());
+                    }
+                    if self.pairing.is_some() {
+                        ui.spinner();
+                        ui.label("Pairing…");
+                    }
+                });
+            });
+
+            ui.collapsing("Pair with QR code (Android 11+)", |ui| {
+                if let Some(pairing) = &self.qr_pairing {
+                    if let Ok(result) = pairing.result.try_recv() {
+                        message = Some(match result {
+                            Ok(output) => output,
+                            Err(e) => format!("Pairing failed: {}", e),
+                        });
+                        self.qr_pairing = None;
+                    }
+                }
+
+                match &self.qr_pairing {
+                    Some(pairing) => {
+                        ui.label("Developer options → Wireless debugging → Pair device with QR code");
+                        paint_qr_code(ui, &pairing.modules, pairing.width);
+                        ui.label(format!("Service: {}", pairing.service_name));
+                        ui.label(format!("Password: {}", pairing.password));
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label("Waiting for the device to scan the code...");
+                        });
+                        if ui.button("Cancel").clicked() {
+                            self.qr_pairing = None;
+                        }
+                    }
+                    None => {
+                        if ui.button("Show QR code").clicked() {
+                            if let Err(e) = self.start_qr_pairing(ui.ctx()) {
+                                message = Some(format!("Error: {}", e));
+                            }
+                        }
+                    }
+                }
+            });
+
+            ui.collapsing("Discovered devices (mDNS)", |ui| {
+                // Only poll adb while the panel is open
+                self.receive_discovered();
+                if self.last_discovery.is_none_or(|t| t.elapsed() >= DISCOVERY_INTERVAL) {
+                    self.refresh_discovered(ui.ctx());
+                }
+                ui.ctx().request_repaint_after(DISCOVERY_INTERVAL);
+
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(self.discovery.is_none(), egui::Button::new("Refresh")).clicked() {
+                        self.refresh_discovered(ui.ctx());
+                    }
+                    if self.discovery.is_some() {
+                        ui.spinner();
+                    }
+                });
+
+                if let Some(error) = &self.discovery_error {
+                    ui.colored_label(egui::Color32::RED, format!("mDNS discovery unavailable: {}", error));
+                } else if self.discovered.is_empty() {
+                    ui.label("No wireless debugging services found on the network");
+                }
+
+                let mut clicked: Option<MdnsService> = None;
+                for service in &self.discovered {
+                    ui.horizontal(|ui| {
+                        let kind = if service.is_pairing() { "pairing" } else { "connect" };
+                        ui.label(format!("{} [{}] {}", service.name, kind, service.address));
+                        let action = if service.is_pairing() { "Pair..." } else { "Connect" };
+                        if ui.add_enabled(can_connect || service.is_pairing(), egui::Button::new(action)).clicked() {
+                            clicked = Some(service.clone());
+                        }
+                    });
+                }
+
+                if let Some(service) = clicked {
+                    if service.is_pairing() {
+                        // Pairing still needs the code shown on the device, so prefill the form
+                        if let Some((host, port)) = service.address.rsplit_once(':') {
+                            self.ip = host.trim_start_matches('[').trim_end_matches(']').to_string();
+                            self.pairing_port = port.to_string();
+                        }
+                        message = Some(format!("Enter the pairing code shown on {} and press Pair", service.name));
+                    } else {
+                        self.connect_to(&service.address, ui.ctx());
+                    }
+                }
+            });
+        });
+
         message
     }
-} 
\ No newline at end of file
+}
+
+// Validates the host and port fields and builds the address adb expects, defaulting to port 5555
+fn connect_address(host: &str, port: &str) -> Result<String, String> {
+    let mut host = host.trim();
+    let mut port = port.trim();
+
+    // Accept "host:port" pasted into the host field when the port field is empty
+    let bracketed = host.starts_with('[') && host.ends_with(']');
+    if port.is_empty() && !bracketed && host.parse::<IpAddr>().is_err() {
+        if let Some((h, p)) = host.rsplit_once(':') {
+            host = h;
+            port = p;
+        }
+    }
+    // IPv6 addresses may be written as "[::1]"
+    let host = host.strip_prefix('[').and_then(|h| h.strip_suffix(']')).unwrap_or(host);
+
+    if host.is_empty() {
+        return Err("host is empty".to_string());
+    }
+
+    let port = if port.is_empty() {
+        DEFAULT_ADB_PORT
+    } else {
+        match port.parse::<u16>() {
+            Ok(p) if p > 0 => p,
+            _ => return Err(format!("port '{}' must be between 1 and 65535", port)),
+        }
+    };
+
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => Ok(format!("{}:{}", ip, port)),
+        Ok(IpAddr::V6(ip)) => Ok(format!("[{}]:{}", ip, port)),
+        Err(_) if is_valid_hostname(host) => Ok(format!("{}:{}", host, port)),
+        Err(_) => Err(format!("'{}' is not an IPv4/IPv6 address or hostname", host)),
+    }
+}
+
+fn is_valid_hostname(host: &str) -> bool {
+    let host = host.strip_suffix('.').unwrap_or(host);
+    // A dotted string of digits is a mistyped IPv4 address, not a hostname
+    if host.split('.').all(|label| label.chars().all(|c| c.is_ascii_digit())) {
+        return false;
+    }
+    host.len() <= 253
+        && host.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+fn paint_qr_code(ui: &mut egui::Ui, modules: &[bool], width: usize) {
+    let module_size = 5.0;
+    // Keep a quiet zone of four modules around the code so scanners can find it
+    let quiet_zone = 4;
+    let side = (width + quiet_zone * 2) as f32 * module_size;
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(side, side), egui::Sense::hover());
+
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 0.0, egui::Color32::WHITE);
+    for (index, dark) in modules.iter().enumerate() {
+        if *dark {
+            let x = (index % width + quiet_zone) as f32 * module_size;
+            let y = (index / width + quiet_zone) as f32 * module_size;
+            let min = rect.min + egui::vec2(x, y);
+            painter.rect_filled(
+                egui::Rect::from_min_size(min, egui::vec2(module_size, module_size)),
+                0.0,
+                egui::Color32::BLACK,
+            );
+        }
+    }
+}
+
+// The pairing password is the only secret in the exchange, so draw it from the OS CSPRNG
+fn random_token(len: usize) -> Result<String, String> {
+    const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+    // Bytes at or above the largest multiple of the alphabet size would favour the first characters
+    let limit = (256 / ALPHABET.len() * ALPHABET.len()) as u8;
+    let mut token = String::with_capacity(len);
+    let mut buffer = [0u8; 32];
+    while token.len() < len {
+        getrandom::getrandom(&mut buffer).map_err(|e| e.to_string())?;
+        token.extend(
+            buffer
+                .iter()
+                .filter(|b| **b < limit)
+                .map(|b| ALPHABET[*b as usize % ALPHABET.len()] as char)
+                .take(len - token.len()),
+        );
+    }
+    Ok(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connect_address_defaults_the_port() {
+        assert_eq!(connect_address("192.168.1.42", ""), Ok("192.168.1.42:5555".to_string()));
+        assert_eq!(connect_address(" 192.168.1.42 ", " 37123 "), Ok("192.168.1.42:37123".to_string()));
+        assert_eq!(connect_address("192.168.1.42:37123", ""), Ok("192.168.1.42:37123".to_string()));
+    }
+
+    #[test]
+    fn connect_address_accepts_ipv6() {
+        assert_eq!(connect_address("fe80::1", ""), Ok("[fe80::1]:5555".to_string()));
+        assert_eq!(connect_address("[fe80::1]", ""), Ok("[fe80::1]:5555".to_string()));
+        assert_eq!(connect_address("[fe80::1]", "37123"), Ok("[fe80::1]:37123".to_string()));
+        assert_eq!(connect_address("[fe80::1]:37123", ""), Ok("[fe80::1]:37123".to_string()));
+    }
+
+    #[test]
+    fn connect_address_rejects_out_of_range_ports() {
+        assert!(connect_address("192.168.1.42", "0").is_err());
+        assert!(connect_address("192.168.1.42", "65536").is_err());
+        assert!(connect_address("192.168.1.42:0", "").is_err());
+        assert!(connect_address("192.168.1.42", "port").is_err());
+        assert_eq!(connect_address("192.168.1.42", "65535"), Ok("192.168.1.42:65535".to_string()));
+    }
+
+    #[test]
+    fn connect_address_validates_hosts() {
+        assert_eq!(connect_address("pixel.local", ""), Ok("pixel.local:5555".to_string()));
+        assert!(connect_address("", "5555").is_err());
+        assert!(connect_address("   ", "").is_err());
+        assert!(connect_address("pixel_7.local", "").is_err());
+    }
+
+    #[test]
+    fn hostname_rules() {
+        assert!(is_valid_hostname("pixel-7"));
+        assert!(is_valid_hostname("build-box.lan."));
+        assert!(!is_valid_hostname("192.168.1.300"));
+        assert!(!is_valid_hostname("-pixel"));
+        assert!(!is_valid_hostname("pixel-"));
+        assert!(!is_valid_hostname("pixel..lan"));
+        assert!(!is_valid_hostname("pixel 7"));
+        assert!(!is_valid_hostname(&"a".repeat(64)));
+        assert!(!is_valid_hostname(&["abc"; 64].join(".")));
+    }
+}
@@ -0,0 +1,190 @@
+use eframe::egui;
+use rfd::FileDialog;
+use serde::{Deserialize, Serialize};
+use crate::adb::Device;
+use crate::crash_monitor::{CrashMonitor, CrashReport};
+use crate::device_context::DeviceContext;
+
+const SETTINGS_KEY: &str = "crash_monitor";
+
+// Off until the user opts in: watching runs a logcat process and polls /data/anr on every device
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct CrashSettings {
+    enabled: bool,
+    watched_packages: String,
+}
+
+pub struct CrashesTab {
+    monitor: CrashMonitor,
+    enabled: bool,
+    // Comma or whitespace separated package names
+    watched_packages: String,
+    selected: Option<u64>,
+}
+
+impl CrashesTab {
+    pub fn new(monitor: CrashMonitor) -> Self {
+        Self {
+            monitor,
+            enabled: false,
+            watched_packages: String::new(),
+            selected: None,
+        }
+    }
+
+    pub fn load(&mut self, storage: &dyn eframe::Storage) {
+        if let Some(settings) = eframe::get_value::<CrashSettings>(storage, SETTINGS_KEY) {
+            self.enabled = settings.enabled;
+            self.watched_packages = settings.watched_packages;
+            self.monitor.set_enabled(self.enabled);
+            self.apply_watched_packages();
+        }
+    }
+
+    pub fn save(&self, storage: &mut dyn eframe::Storage) {
+        let settings = CrashSettings {
+            enabled: self.enabled,
+            watched_packages: self.watched_packages.clone(),
+        };
+        eframe::set_value(storage, SETTINGS_KEY, &settings);
+    }
+
+    // Called every frame, whichever tab is showing, so every device stays watched
+    pub fn update(&mut self, devices: &[Device]) {
+        self.monitor.sync_devices(devices);
+    }
+
+    pub fn unseen_count(&self) -> usize {
+        self.monitor.unseen_count()
+    }
+
+    fn apply_watched_packages(&self) {
+        let packages = self
+            .watched_packages
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|p| !p.is_empty())
+            .map(str::to_string)
+            .collect();
+        self.monitor.set_watched_packages(packages);
+    }
+
+    fn save_report(report: &CrashReport) -> Option<String> {
+        let name = format!("{}-{}.txt", report.kind, if report.package.is_empty() { "unknown" } else { &report.package })
+            .replace(' ', "_");
+        let path = FileDialog::new()
+            .set_title("Save stack trace")
+            .set_file_name(&name)
+            .save_file()?;
+        let content = format!(
+            "{} on {} at {}\nPackage: {}\n{}\n\n{}\n",
+            report.kind, report.device_id, report.time, report.package, report.summary, report.trace
+        );
+        Some(match std::fs::write(&path, content) {
+            Ok(_) => format!("Saved stack trace to {}", path.display()),
+            Err(e) => format!("Error: saving {}: {}", path.display(), e),
+        })
+    }
+
+    fn show_report(&self, ui: &mut egui::Ui, report: &CrashReport) -> Option<String> {
+        let mut message = None;
+        ui.horizontal(|ui| {
+            ui.strong(format!("{} in {}", report.kind, if report.package.is_empty() { "unknown package" } else { &report.package }));
+            if ui.button("Copy").clicked() {
+                ui.output_mut(|o| o.copied_text = report.trace.clone());
+                message = Some("Stack trace copied to the clipboard".to_string());
+            }
+            if ui.button("Save…").clicked() {
+                message = Self::save_report(report);
+            }
+        });
+        ui.label(&report.summary);
+
+        let mut trace = report.trace.as_str();
+        egui::ScrollArea::both().id_source("crash_trace").show(ui, |ui| {
+            ui.add(
+                egui::TextEdit::multiline(&mut trace)
+                    .font(egui::TextStyle::Monospace)
+                    .desired_width(f32::INFINITY),
+            );
+        });
+        message
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, context: &mut DeviceContext) -> Option<String> {
+        ui.heading("Crashes & ANRs");
+
+        ui.horizontal(|ui| {
+            if ui.checkbox(&mut self.enabled, "Watch connected devices")
+                .on_hover_text("Runs logcat on every usable device and checks /data/anr for new traces")
+                .changed()
+            {
+                self.monitor.set_enabled(self.enabled);
+            }
+            if self.enabled {
+                ui.weak(format!("{} device(s) watched", self.monitor.watched_devices()));
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Packages:");
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.watched_packages)
+                    .desired_width(360.0)
+                    .hint_text("All packages when empty, e.g. com.example.app"),
+            );
+            if response.changed() {
+                self.apply_watched_packages();
+            }
+        });
+
+        let reports = self.monitor.reports();
+        ui.horizontal(|ui| {
+            if ui.button("Mark all seen").clicked() {
+                for report in &reports {
+                    self.monitor.mark_seen(report.id);
+                }
+            }
+            if ui.button("Clear").clicked() {
+                self.monitor.clear();
+                self.selected = None;
+            }
+        });
+        ui.separator();
+
+        if reports.is_empty() {
+            ui.label("No crashes or ANRs detected yet");
+            return None;
+        }
+
+        egui::ScrollArea::vertical().id_source("crash_list").max_height(160.0).show(ui, |ui| {
+            for report in reports.iter().rev() {
+                let package = if report.package.is_empty() { "?" } else { &report.package };
+                let text = format!(
+                    "{}  {}  {}  {}: {}",
+                    report.time,
+                    context.labels.display_name(&report.device_id),
+                    report.kind,
+                    package,
+                    report.summary
+                );
+                let mut text = egui::RichText::new(text).color(egui::Color32::from_rgb(240, 80, 80));
+                if !report.seen {
+                    text = text.strong();
+                }
+                if ui.selectable_label(self.selected == Some(report.id), text).clicked() {
+                    self.selected = Some(report.id);
+                    self.monitor.mark_seen(report.id);
+                }
+            }
+        });
+
+        ui.separator();
+        match reports.iter().find(|r| Some(r.id) == self.selected) {
+            Some(report) => self.show_report(ui, report),
+            None => {
+                ui.label("Select a report to see its stack trace");
+                None
+            }
+        }
+    }
+}
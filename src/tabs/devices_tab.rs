@@ -1,69 +1,563 @@
 use eframe::egui;
+use std::collections::HashMap;
 use std::process::Command;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use crate::adb::{AdbManager, Device, DeviceDetails, DeviceState};
+use crate::device_context::DeviceContext;
+use crate::device_labels::{DeviceLabel, DeviceLabels};
 
-#[derive(Clone)]
-pub struct Device {
-    pub id: String,
-    pub status: String,
+const WIRELESS_PORT: u16 = 5555;
+// root, remount and disable-verity restart adbd and can take a while on slow devices
+const ACTION_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, PartialEq)]
+enum DeviceAction {
+    Reboot,
+    RebootBootloader,
+    RebootRecovery,
+    RebootSideload,
+    Root,
+    Unroot,
+    Remount,
+    DisableVerity,
+    KillServer,
+    StartServer,
+}
+
+impl DeviceAction {
+    const DEVICE_ACTIONS: [DeviceAction; 8] = [
+        DeviceAction::Reboot,
+        DeviceAction::RebootBootloader,
+        DeviceAction::RebootRecovery,
+        DeviceAction::RebootSideload,
+        DeviceAction::Root,
+        DeviceAction::Unroot,
+        DeviceAction::Remount,
+        DeviceAction::DisableVerity,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            DeviceAction::Reboot => "Reboot",
+            DeviceAction::RebootBootloader => "Reboot to bootloader",
+            DeviceAction::RebootRecovery => "Reboot to recovery",
+            DeviceAction::RebootSideload => "Reboot to sideload",
+            DeviceAction::Root => "Restart adbd as root",
+            DeviceAction::Unroot => "Restart adbd without root",
+            DeviceAction::Remount => "Remount system partitions read-write",
+            DeviceAction::DisableVerity => "Disable verity",
+            DeviceAction::KillServer => "Kill server",
+            DeviceAction::StartServer => "Start server",
+        }
+    }
+
+    fn args(&self) -> &'static [&'static str] {
+        match self {
+            DeviceAction::Reboot => &["reboot"],
+            DeviceAction::RebootBootloader => &["reboot", "bootloader"],
+            DeviceAction::RebootRecovery => &["reboot", "recovery"],
+            DeviceAction::RebootSideload => &["reboot", "sideload"],
+            DeviceAction::Root => &["root"],
+            DeviceAction::Unroot => &["unroot"],
+            DeviceAction::Remount => &["remount"],
+            DeviceAction::DisableVerity => &["disable-verity"],
+            DeviceAction::KillServer => &["kill-server"],
+            DeviceAction::StartServer => &["start-server"],
+        }
+    }
+
+    // Actions that interrupt the device or every connection ask before running
+    fn needs_confirmation(&self) -> bool {
+        !matches!(self, DeviceAction::StartServer)
+    }
+}
+
+fn go_wireless(adb_manager: &AdbManager, device: &str) -> String {
+    // Read the address first, adbd restarts once it switches to TCP/IP
+    let ip = match adb_manager.get_wlan_ip(device) {
+        Ok(ip) => ip,
+        Err(e) => return format!("{}: {}", device, e),
+    };
+    if let Err(e) = adb_manager.tcpip(device, WIRELESS_PORT) {
+        return format!("{}: failed to switch to TCP/IP mode: {}", device, e.trim());
+    }
+
+    let address = format!("{}:{}", ip, WIRELESS_PORT);
+    let mut result = None;
+    for _ in 0..5 {
+        thread::sleep(Duration::from_secs(1));
+        let attempt = adb_manager.connect(&address);
+        let connected = attempt.is_connected();
+        result = Some(attempt);
+        if connected {
+            break;
+        }
+    }
+
+    match result {
+        Some(result) if result.is_connected() => {
+            format!("{} is now available wirelessly as {}", device, address)
+        }
+        Some(result) => format!("{}: {}", device, result),
+        None => format!("{}: could not connect to {}", device, address),
+    }
 }
 
 pub struct DevicesTab {
+    adb_manager: Arc<AdbManager>,
     devices: Vec<Device>,
+    details: HashMap<String, Result<DeviceDetails, String>>,
+    loading_details: HashMap<String, Receiver<Result<DeviceDetails, String>>>,
+    pending_action: Option<(Option<String>, DeviceAction)>,
+    editing_label: Option<(String, DeviceLabel)>,
+    refresh_requested: bool,
+    going_wireless: HashMap<String, Receiver<String>>,
+    // Messages from actions running on worker threads, one per action
+    action_results: Receiver<String>,
+    action_sender: Sender<String>,
+    running_actions: usize,
 }
 
 impl Default for DevicesTab {
     fn default() -> Self {
+        Self::new(Arc::new(AdbManager::new()))
+    }
+}
+
+impl DevicesTab {
+    pub fn new(adb_manager: Arc<AdbManager>) -> Self {
+        let (action_sender, action_results) = mpsc::channel();
         Self {
+            adb_manager,
             devices: Vec::new(),
+            details: HashMap::new(),
+            loading_details: HashMap::new(),
+            pending_action: None,
+            editing_label: None,
+            refresh_requested: false,
+            going_wireless: HashMap::new(),
+            action_results,
+            action_sender,
+            running_actions: 0,
         }
     }
-}
 
-impl DevicesTab {
-    pub fn refresh_devices(&mut self) {
-        self.devices.clear();
-        
-        if let Ok(output) = Command::new("adb").args(["devices"]).output() {
-            let output_str = String::from_utf8_lossy(&output.stdout);
-            for line in output_str.lines().skip(1) {
-                if !line.trim().is_empty() {
-                    let parts: Vec<&str> = line.split_whitespace().collect();
-                    if parts.len() >= 2 {
-                        self.devices.push(Device {
-                            id: parts[0].to_string(),
-                            status: parts[1].to_string(),
-                        });
-                    }
+    // The shared device list is refreshed at the end of the frame
+    fn refresh_devices(&mut self) {
+        self.refresh_requested = true;
+    }
+
+    // Other entries in the list that belong to the same physical device
+    fn linked_devices(&self, device: &Device, labels: &DeviceLabels) -> Vec<String> {
+        match labels.hardware_serial(&device.id) {
+            Some(serial) => self
+                .devices
+                .iter()
+                .filter(|d| d.id != device.id && labels.hardware_serial(&d.id) == Some(serial))
+                .map(|d| d.id.clone())
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // Switching takes several adb round trips and connect retries, so it runs on a worker thread
+    fn start_go_wireless(&mut self, device: &str, ctx: &egui::Context) {
+        let (sender, receiver) = mpsc::channel();
+        let adb_manager = self.adb_manager.clone();
+        let thread_device = device.to_string();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let _ = sender.send(go_wireless(&adb_manager, &thread_device));
+            ctx.request_repaint();
+        });
+        self.going_wireless.insert(device.to_string(), receiver);
+    }
+
+    // Results for every pending switch, even when the USB entry has already left the list
+    fn receive_go_wireless(&mut self) -> Option<String> {
+        let mut message = None;
+        self.going_wireless.retain(|_, receiver| match receiver.try_recv() {
+            Ok(result) => {
+                message = Some(result);
+                false
+            }
+            Err(mpsc::TryRecvError::Empty) => true,
+            Err(mpsc::TryRecvError::Disconnected) => false,
+        });
+        if message.is_some() {
+            self.refresh_devices();
+        }
+        message
+    }
+
+    // Reading details takes about six adb round trips, so it runs on a worker thread
+    fn load_details(&mut self, device: &str, ctx: &egui::Context) {
+        let (sender, receiver) = mpsc::channel();
+        let adb_manager = self.adb_manager.clone();
+        let thread_device = device.to_string();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let _ = sender.send(adb_manager.get_device_details(&thread_device));
+            ctx.request_repaint();
+        });
+        self.loading_details.insert(device.to_string(), receiver);
+    }
+
+    fn receive_details(&mut self) {
+        let details = &mut self.details;
+        self.loading_details.retain(|device, receiver| match receiver.try_recv() {
+            Ok(result) => {
+                details.insert(device.clone(), result);
+                false
+            }
+            Err(mpsc::TryRecvError::Empty) => true,
+            Err(mpsc::TryRecvError::Disconnected) => false,
+        });
+    }
+
+    // Runs on a worker thread; the result arrives through `receive_action_results`
+    fn run_action(&mut self, device: Option<&str>, action: DeviceAction, ctx: &egui::Context) {
+        let mut args: Vec<String> = Vec::new();
+        if let Some(device) = device {
+            args.extend(["-s".to_string(), device.to_string()]);
+        }
+        args.extend(action.args().iter().map(|a| a.to_string()));
+
+        let target = device.unwrap_or("adb server").to_string();
+        self.spawn_action(ctx, move |adb_manager| {
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            match adb_manager.run_adb_command_with_timeout(&args, ACTION_TIMEOUT) {
+                Ok((true, stdout, _)) if stdout.trim().is_empty() => format!("{}: {} done", target, action.label()),
+                Ok((true, stdout, _)) => format!("{}: {}: {}", target, action.label(), stdout.trim()),
+                Ok((false, stdout, stderr)) => {
+                    let output = if stderr.trim().is_empty() { stdout } else { stderr };
+                    format!("{}: {} failed: {}", target, action.label(), output.trim())
                 }
+                Err(e) => format!("{}: {} failed: {}", target, action.label(), e.trim()),
             }
+        });
+    }
+
+    // Runs `job` on a worker thread; its message arrives through `receive_action_results`
+    fn spawn_action(&mut self, ctx: &egui::Context, job: impl FnOnce(&AdbManager) -> String + Send + 'static) {
+        let adb_manager = self.adb_manager.clone();
+        let sender = self.action_sender.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let _ = sender.send(job(&adb_manager));
+            ctx.request_repaint();
+        });
+        self.running_actions += 1;
+    }
+
+    fn receive_action_results(&mut self) -> Option<String> {
+        let mut message = None;
+        for result in self.action_results.try_iter() {
+            self.running_actions = self.running_actions.saturating_sub(1);
+            message = Some(result);
+        }
+        if message.is_some() {
+            self.refresh_devices();
+        }
+        message
+    }
+
+    fn request_action(&mut self, device: Option<&str>, action: DeviceAction, ctx: &egui::Context) {
+        if action.needs_confirmation() {
+            self.pending_action = Some((device.map(str::to_string), action));
+        } else {
+            self.run_action(device, action, ctx);
         }
     }
 
-    pub fn show(&mut self, ui: &mut egui::Ui) -> Option<String> {
+    fn show_confirmation(&mut self, ctx: &egui::Context) {
+        let Some((device, action)) = self.pending_action.clone() else {
+            return;
+        };
+        let mut close = false;
+
+        egui::Window::new("Confirm")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                match &device {
+                    Some(device) => ui.label(format!("{} on {}?", action.label(), device)),
+                    None => ui.label(format!("{}? Every device connection will be dropped.", action.label())),
+                };
+                ui.horizontal(|ui| {
+                    if ui.button("Yes").clicked() {
+                        self.run_action(device.as_deref(), action, ctx);
+                        close = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        close = true;
+                    }
+                });
+            });
+
+        if close {
+            self.pending_action = None;
+        }
+    }
+
+    fn show_label_editor(&mut self, ctx: &egui::Context, labels: &mut DeviceLabels) {
+        let Some((device, label)) = &mut self.editing_label else {
+            return;
+        };
+        let mut close = false;
+
+        egui::Window::new(format!("Label {}", device))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                egui::Grid::new("label_editor").num_columns(2).show(ui, |ui| {
+                    ui.label("Nickname:");
+                    ui.text_edit_singleline(&mut label.nickname);
+                    ui.end_row();
+
+                    ui.label("Group:");
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut label.group);
+                        ui.menu_button("▼", |ui| {
+                            for group in labels.groups() {
+                                if ui.button(&group).clicked() {
+                                    label.group = group;
+                                    ui.close_menu();
+                                }
+                            }
+                        });
+                    });
+                    ui.end_row();
+
+                    ui.label("Color:");
+                    ui.horizontal(|ui| {
+                        let mut enabled = label.color.is_some();
+                        if ui.checkbox(&mut enabled, "").changed() {
+                            label.color = enabled.then_some([90, 170, 255]);
+                        }
+                        if let Some(color) = &mut label.color {
+                            ui.color_edit_button_srgb(color);
+                        }
+                    });
+                    ui.end_row();
+                });
+
+                ui.horizontal(|ui| {
+                    if ui.button("Save").clicked() {
+                        label.nickname = label.nickname.trim().to_string();
+                        label.group = label.group.trim().to_string();
+                        labels.set(device, label.clone());
+                        close = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        close = true;
+                    }
+                });
+            });
+
+        if close {
+            self.editing_label = None;
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, context: &mut DeviceContext) -> Option<String> {
         ui.heading("Connected Devices");
-        
-        if ui.button("Refresh").clicked() {
-            self.refresh_devices();
+
+        self.devices = context.devices.clone();
+        self.details.retain(|id, _| self.devices.iter().any(|d| &d.id == id));
+        self.loading_details.retain(|id, _| self.devices.iter().any(|d| &d.id == id));
+        self.receive_details();
+        self.show_confirmation(ui.ctx());
+        let mut message = self.receive_action_results();
+        if let Some(result) = self.receive_go_wireless() {
+            message = Some(result);
         }
-        
-        let devices_to_process: Vec<Device> = self.devices.clone();
-        let mut message = None;
+        self.show_label_editor(ui.ctx(), &mut context.labels);
 
-        for device in devices_to_process {
-            ui.horizontal(|ui| {
-                ui.label(format!("{} ({})", device.id, device.status));
-                if ui.button("Disconnect").clicked() {
-                    if let Ok(output) = Command::new("adb")
-                        .args(["disconnect", &device.id])
-                        .output()
+        ui.horizontal(|ui| {
+            if ui.button("Refresh").clicked() {
+                self.refresh_devices();
+            }
+            ui.separator();
+            ui.label("Host:");
+            for action in [DeviceAction::KillServer, DeviceAction::StartServer] {
+                if ui.button(action.label()).clicked() {
+                    self.request_action(None, action, ui.ctx());
+                }
+            }
+            if self.running_actions > 0 {
+                ui.spinner();
+                ui.label(format!("Running {} action(s)…", self.running_actions));
+            }
+        });
+
+        let mut devices_to_process: Vec<Device> = self.devices.clone();
+        // Grouped devices first, ungrouped ones at the end
+        devices_to_process.sort_by_key(|d| (context.labels.group(&d.id).is_empty(), context.labels.group(&d.id).to_string()));
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            let mut current_group = None;
+            for device in devices_to_process {
+                let group = context.labels.group(&device.id).to_string();
+                if current_group.as_ref() != Some(&group) {
+                    ui.separator();
+                    ui.strong(if group.is_empty() { "Ungrouped" } else { group.as_str() });
+                    current_group = Some(group);
+                }
+
+                ui.horizontal(|ui| {
+                    let name = format!("{} ({})", context.labels.display_name(&device.id), device.state);
+                    if device.is_usable() {
+                        let mut in_set = context.selection.is_in_set(&device.id);
+                        if ui.checkbox(&mut in_set, "").on_hover_text("Include in multi-device actions").changed() {
+                            context.toggle_in_set(&device.id);
+                        }
+                        let is_active = context.selection.active() == Some(device.id.as_str());
+                        let name = context.labels.rich_text(&device.id, name);
+                        if ui.selectable_label(is_active, name).on_hover_text("Make this the active device").clicked() {
+                            context.set_active(&device.id);
+                        }
+                    } else {
+                        ui.colored_label(egui::Color32::from_rgb(230, 160, 40), name);
+                    }
+                    let linked = self.linked_devices(&device, &context.labels);
+                    if !linked.is_empty() {
+                        ui.weak(format!("same device as {}", linked.join(", ")));
+                    }
+                    if self.going_wireless.contains_key(&device.id) {
+                        ui.spinner();
+                        ui.label("Switching to Wi-Fi…");
+                    } else if !device.is_wireless()
+                        && device.is_usable()
+                        && linked.is_empty()
+                        && ui.button("Go wireless").on_hover_text("Switch to TCP/IP mode and connect over Wi-Fi").clicked()
                     {
-                        message = Some(String::from_utf8_lossy(&output.stdout).to_string());
-                        self.refresh_devices();
+                        self.start_go_wireless(&device.id, ui.ctx());
+                    }
+                    if ui.button("Label").on_hover_text("Set a nickname, color and group").clicked() {
+                        let label = context.labels.get(&device.id).cloned().unwrap_or_default();
+                        self.editing_label = Some((device.id.clone(), label));
                     }
+                    ui.menu_button("Actions", |ui| {
+                        for action in DeviceAction::DEVICE_ACTIONS {
+                            if ui.button(action.label()).clicked() {
+                                self.request_action(Some(&device.id), action, ui.ctx());
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                    // Disconnecting only means something for TCP/IP connections
+                    if device.is_wireless() && ui.button("Disconnect").clicked() {
+                        if let Ok(output) = Command::new("adb")
+                            .args(["disconnect", &device.id])
+                            .output()
+                        {
+                            message = Some(String::from_utf8_lossy(&output.stdout).to_string());
+                            self.refresh_devices();
+                        }
+                    }
+                });
+
+                if let Some(hint) = device.state_hint() {
+                    ui.horizontal_wrapped(|ui| {
+                        ui.weak(hint);
+                        match device.state {
+                            DeviceState::Unauthorized
+                                if ui.small_button("Retry auth").on_hover_text("Restart the adb server so the device prompts again").clicked() =>
+                            {
+                                self.spawn_action(ui.ctx(), |adb_manager| match adb_manager.restart_server() {
+                                    Ok(_) => "adb server restarted, check the device for the authorization prompt".to_string(),
+                                    Err(e) => format!("Failed to restart adb server: {}", e.trim()),
+                                });
+                            }
+                            // Reconnecting a wireless device can wait up to CONNECT_TIMEOUT
+                            DeviceState::Offline if ui.small_button("Reconnect").clicked() => {
+                                let device = device.clone();
+                                self.spawn_action(ui.ctx(), move |adb_manager| match adb_manager.reconnect_offline(&device) {
+                                    Ok(output) => format!("{}: {}", device.id, output.trim()),
+                                    Err(e) => format!("{}: reconnect failed: {}", device.id, e.trim()),
+                                });
+                            }
+                            _ => {}
+                        }
+                    });
                 }
-            });
+
+                if device.is_usable() {
+                    egui::CollapsingHeader::new("Details")
+                        .id_source(("device_details", &device.id))
+                        .show(ui, |ui| self.show_details(ui, &device.id));
+                }
+            }
+        });
+
+        if self.refresh_requested {
+            self.refresh_requested = false;
+            context.refresh();
         }
-        
         message
     }
-} 
\ No newline at end of file
+
+    fn show_details(&mut self, ui: &mut egui::Ui, device: &str) {
+        // Details are fetched the first time the section is expanded
+        let loading = self.loading_details.contains_key(device);
+        if ui.add_enabled(!loading, egui::Button::new("Reload").small()).clicked() {
+            self.details.remove(device);
+        }
+        if !loading && !self.details.contains_key(device) {
+            self.load_details(device, ui.ctx());
+        }
+        if self.loading_details.contains_key(device) {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Reading device details…");
+            });
+            return;
+        }
+        let details = match self.details.get(device) {
+            None => return,
+            Some(Ok(details)) => details,
+            Some(Err(e)) => {
+                ui.colored_label(egui::Color32::RED, format!("Failed to read device details: {}", e.trim()));
+                return;
+            }
+        };
+
+        egui::Grid::new(("device_details_grid", device))
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                let rows = [
+                    ("Manufacturer", &details.manufacturer),
+                    ("Model", &details.model),
+                    ("Android version", &details.android_version),
+                    ("SDK level", &details.sdk_level),
+                    ("Build fingerprint", &details.fingerprint),
+                    ("ABIs", &details.abis),
+                    ("Serial", &details.serial),
+                    ("Screen resolution", &details.screen_size),
+                    ("Screen density", &details.screen_density),
+                    ("Battery level", &details.battery_level),
+                    ("Battery temperature", &details.battery_temperature),
+                    ("Storage (/data)", &details.storage_free),
+                ];
+                for (name, value) in rows {
+                    ui.label(name);
+                    ui.label(if value.is_empty() { "-" } else { value.as_str() });
+                    ui.end_row();
+                }
+                ui.label("IP addresses");
+                ui.label(if details.ip_addresses.is_empty() {
+                    "-".to_string()
+                } else {
+                    details.ip_addresses.join("\n")
+                });
+                ui.end_row();
+            });
+    }
+}
@@ -1,6 +1,7 @@
 use eframe::egui;
 use std::sync::Arc;
 use crate::adb::AdbManager;
+use crate::device_context::DeviceContext;
 use std::path::PathBuf;
 use rfd::FileDialog;
 
@@ -142,27 +143,28 @@ impl FileManagerTab {
         }
     }
 
-    pub fn show(&mut self, ui: &mut egui::Ui) -> Option<String> {
+    pub fn show(&mut self, ui: &mut egui::Ui, context: &mut DeviceContext) -> Option<String> {
+        let active = context.selection.active().unwrap_or_default().to_string();
+        if active != self.current_device {
+            self.current_device = active;
+            self.selected_file = None;
+            self.update_file_list();
+        }
+
         ui.vertical(|ui| {
             // Header section
             ui.heading("File Manager");
             
             // Controls section - using a fixed area for controls
             egui::Frame::none().show(ui, |ui| {
-                // Device selection
+                // Device selection, shared with the selector in the top panel
                 ui.horizontal(|ui| {
                     ui.label("Device:");
-                    if let Ok(devices) = self.adb_manager.get_devices() {
-                        egui::ComboBox::from_label("")
-                            .selected_text(&self.current_device)
-                            .show_ui(ui, |ui| {
-                                for device in devices {
-                                    if ui.selectable_label(self.current_device == device, &device).clicked() {
-                                        self.current_device = device;
-                                        self.update_file_list();
-                                    }
-                                }
-                            });
+                    context.device_picker(ui, "file_manager_device");
+
+                    // The selected device may have gone offline or lost authorization since it was picked
+                    if let Some(device) = context.active_device().filter(|d| !d.is_usable()) {
+                        ui.colored_label(egui::Color32::from_rgb(230, 160, 40), format!("Device is {}", device.state));
                     }
                 });
 
@@ -0,0 +1,332 @@
+use eframe::egui;
+use rfd::FileDialog;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use crate::adb::AdbManager;
+use crate::apk_install::{self, explain_failure, is_bundle, is_installable, plan_install};
+use crate::device_context::DeviceContext;
+
+enum InstallEvent {
+    Prepared,
+    PrepareFailed(String),
+    // Device, install unit name and adb's result
+    Unit(String, String, Result<String, String>),
+    DeviceDone(String),
+}
+
+// Install unit name and adb's result, in the order the units ran
+type UnitResults = Vec<(String, Result<String, String>)>;
+
+struct InstallJob {
+    devices: Vec<String>,
+    preparing: bool,
+    error: Option<String>,
+    results: BTreeMap<String, UnitResults>,
+    done: BTreeSet<String>,
+    receiver: Receiver<InstallEvent>,
+}
+
+impl InstallJob {
+    fn is_running(&self) -> bool {
+        self.error.is_none() && self.done.len() < self.devices.len()
+    }
+}
+
+pub struct InstallTab {
+    adb_manager: Arc<AdbManager>,
+    files: Vec<PathBuf>,
+    replace: bool,
+    downgrade: bool,
+    grant_permissions: bool,
+    allow_test: bool,
+    // Install the picked .apk files together as the splits of one app
+    as_splits: bool,
+    job: Option<InstallJob>,
+}
+
+impl Default for InstallTab {
+    fn default() -> Self {
+        Self::new(Arc::new(AdbManager::new()))
+    }
+}
+
+impl InstallTab {
+    pub fn new(adb_manager: Arc<AdbManager>) -> Self {
+        Self {
+            adb_manager,
+            files: Vec::new(),
+            replace: true,
+            downgrade: false,
+            grant_permissions: false,
+            allow_test: false,
+            as_splits: false,
+            job: None,
+        }
+    }
+
+    fn add_files(&mut self, paths: impl IntoIterator<Item = PathBuf>) {
+        for path in paths {
+            if is_installable(&path) && !self.files.contains(&path) {
+                self.files.push(path);
+            }
+        }
+    }
+
+    fn flags(&self) -> Vec<&'static str> {
+        [
+            (self.replace, "-r"),
+            (self.downgrade, "-d"),
+            (self.grant_permissions, "-g"),
+            (self.allow_test, "-t"),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, flag)| enabled.then_some(flag))
+        .collect()
+    }
+
+    fn start_install(&mut self, devices: Vec<String>, ctx: &egui::Context) {
+        let (sender, receiver) = mpsc::channel();
+        let adb_manager = self.adb_manager.clone();
+        let files = self.files.clone();
+        let as_splits = self.as_splits;
+        let flags = self.flags();
+        let thread_devices = devices.clone();
+        let ctx = ctx.clone();
+
+        // Bundles are unpacked once, then every device installs in parallel
+        thread::spawn(move || {
+            let work_dir = apk_install::work_dir();
+            let units = match plan_install(&files, as_splits, &work_dir) {
+                Ok(units) => Arc::new(units),
+                Err(e) => {
+                    let _ = sender.send(InstallEvent::PrepareFailed(e));
+                    let _ = std::fs::remove_dir_all(&work_dir);
+                    ctx.request_repaint();
+                    return;
+                }
+            };
+            let _ = sender.send(InstallEvent::Prepared);
+            ctx.request_repaint();
+
+            let handles: Vec<_> = thread_devices
+                .into_iter()
+                .map(|device_id| {
+                    let adb_manager = adb_manager.clone();
+                    let units = units.clone();
+                    let flags = flags.clone();
+                    let sender = sender.clone();
+                    let ctx = ctx.clone();
+                    thread::spawn(move || {
+                        for unit in units.iter() {
+                            let mut result = adb_manager.install(&device_id, &unit.apks, &flags);
+                            if result.is_ok() {
+                                for (local, remote) in &unit.obbs {
+                                    if let Err(e) = adb_manager.push(&device_id, local, remote) {
+                                        result = Err(format!("Installed, but copying {} failed: {}", remote, e.trim()));
+                                        break;
+                                    }
+                                }
+                            }
+                            let _ = sender.send(InstallEvent::Unit(device_id.clone(), unit.name.clone(), result));
+                            ctx.request_repaint();
+                        }
+                        let _ = sender.send(InstallEvent::DeviceDone(device_id));
+                        ctx.request_repaint();
+                    })
+                })
+                .collect();
+            for handle in handles {
+                let _ = handle.join();
+            }
+            let _ = std::fs::remove_dir_all(&work_dir);
+        });
+
+        self.job = Some(InstallJob {
+            devices,
+            preparing: true,
+            error: None,
+            results: BTreeMap::new(),
+            done: BTreeSet::new(),
+            receiver,
+        });
+    }
+
+    fn receive(&mut self) {
+        let Some(job) = &mut self.job else {
+            return;
+        };
+        for event in job.receiver.try_iter() {
+            match event {
+                InstallEvent::Prepared => job.preparing = false,
+                InstallEvent::PrepareFailed(e) => {
+                    job.preparing = false;
+                    job.error = Some(e);
+                }
+                InstallEvent::Unit(device_id, unit, result) => {
+                    job.results.entry(device_id).or_default().push((unit, result));
+                }
+                InstallEvent::DeviceDone(device_id) => {
+                    job.done.insert(device_id);
+                }
+            }
+        }
+    }
+
+    fn show_files(&mut self, ui: &mut egui::Ui) {
+        let dropped: Vec<PathBuf> = ui.ctx().input(|i| i.raw.dropped_files.iter().filter_map(|f| f.path.clone()).collect());
+        self.add_files(dropped);
+        let hovering = ui.ctx().input(|i| !i.raw.hovered_files.is_empty());
+
+        let stroke = if hovering {
+            egui::Stroke::new(2.0, ui.visuals().selection.bg_fill)
+        } else {
+            ui.visuals().widgets.noninteractive.bg_stroke
+        };
+        egui::Frame::group(ui.style()).stroke(stroke).show(ui, |ui| {
+            ui.set_width(ui.available_width());
+            if self.files.is_empty() {
+                ui.label("Drop .apk, .apks or .xapk files here, or add them with the button below");
+            }
+            let mut removed = None;
+            for (index, file) in self.files.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui.small_button("✖").clicked() {
+                        removed = Some(index);
+                    }
+                    ui.label(file.display().to_string());
+                    if is_bundle(file) {
+                        ui.weak("(bundle)");
+                    }
+                });
+            }
+            if let Some(index) = removed {
+                self.files.remove(index);
+            }
+        });
+
+        ui.horizontal(|ui| {
+            if ui.button("Add files…").clicked() {
+                if let Some(paths) = FileDialog::new()
+                    .set_title("Select APKs to install")
+                    .add_filter("Android packages", &["apk", "apks", "xapk"])
+                    .pick_files()
+                {
+                    self.add_files(paths);
+                }
+            }
+            if ui.button("Clear").clicked() {
+                self.files.clear();
+            }
+        });
+    }
+
+    fn show_options(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.replace, "Replace existing (-r)");
+            ui.checkbox(&mut self.downgrade, "Allow downgrade (-d)");
+            ui.checkbox(&mut self.grant_permissions, "Grant runtime permissions (-g)");
+            ui.checkbox(&mut self.allow_test, "Allow test packages (-t)");
+        });
+        let loose_apks = self.files.iter().filter(|f| !is_bundle(f)).count();
+        if loose_apks > 1 {
+            ui.checkbox(&mut self.as_splits, "The .apk files are splits of one app (install-multiple)")
+                .on_hover_text("Otherwise every .apk is installed as a separate app");
+        }
+    }
+
+    fn show_results(&self, ui: &mut egui::Ui, context: &DeviceContext) {
+        let Some(job) = &self.job else {
+            return;
+        };
+        ui.separator();
+        if let Some(error) = &job.error {
+            ui.colored_label(egui::Color32::RED, format!("Could not prepare the install: {}", error));
+            return;
+        }
+        if job.preparing {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Unpacking bundles…");
+            });
+            return;
+        }
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("install_results").num_columns(3).striped(true).show(ui, |ui| {
+                ui.strong("Device");
+                ui.strong("Package");
+                ui.strong("Result");
+                ui.end_row();
+
+                for device_id in &job.devices {
+                    let name = context.labels.rich_text(device_id, context.labels.display_name(device_id));
+                    for (unit, result) in job.results.get(device_id).into_iter().flatten() {
+                        ui.label(name.clone());
+                        ui.label(unit);
+                        match result {
+                            Ok(_) => {
+                                ui.colored_label(egui::Color32::GREEN, "Success");
+                            }
+                            Err(output) => {
+                                ui.vertical(|ui| match explain_failure(output) {
+                                    Some((code, explanation)) => {
+                                        ui.colored_label(egui::Color32::RED, explanation);
+                                        ui.weak(code).on_hover_text(output);
+                                    }
+                                    None => {
+                                        ui.colored_label(egui::Color32::RED, format!("Failed: {}", output));
+                                    }
+                                });
+                            }
+                        }
+                        ui.end_row();
+                    }
+                    if !job.done.contains(device_id) {
+                        ui.label(name);
+                        ui.spinner();
+                        ui.label("Installing…");
+                        ui.end_row();
+                    }
+                }
+            });
+        });
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, context: &mut DeviceContext) -> Option<String> {
+        ui.heading("Install APKs");
+        self.receive();
+
+        self.show_files(ui);
+        self.show_options(ui);
+
+        ui.separator();
+        let targets: Vec<String> = context
+            .target_devices()
+            .into_iter()
+            .filter(|d| d.is_usable())
+            .map(|d| d.id)
+            .collect();
+        ui.horizontal(|ui| {
+            let running = self.job.as_ref().is_some_and(InstallJob::is_running);
+            let enabled = !running && !self.files.is_empty() && !targets.is_empty();
+            if ui.add_enabled(enabled, egui::Button::new("Install"))
+                .on_hover_text("Installs on the multi-selected devices, or the active device")
+                .clicked()
+            {
+                self.start_install(targets.clone(), ui.ctx());
+            }
+            let names: Vec<String> = targets.iter().map(|d| context.labels.display_name(d)).collect();
+            if names.is_empty() {
+                ui.weak("No usable device selected");
+            } else {
+                ui.label(format!("Target: {}", names.join(", ")));
+            }
+        });
+
+        self.show_results(ui, context);
+        None
+    }
+}
@@ -0,0 +1,459 @@
+use eframe::egui;
+use rfd::FileDialog;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::adb::AdbManager;
+use crate::device_context::DeviceContext;
+use crate::logcat::{read_log_file, LogBuffer, LogEntry, LogFilter, LogLevel, LogcatStream};
+
+// Oldest lines are dropped beyond this so memory stays flat on long sessions
+const MAX_LINES: usize = 20_000;
+const PACKAGE_PID_INTERVAL: Duration = Duration::from_secs(2);
+
+// Raw lines from the live stream written to a file as they arrive
+struct Recording {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    lines: usize,
+}
+
+pub struct LogcatTab {
+    adb_manager: Arc<AdbManager>,
+    // Device the running stream reads from
+    device_id: Option<String>,
+    buffers: Vec<LogBuffer>,
+    stream: Option<LogcatStream>,
+    stream_status: String,
+    recording: Option<Recording>,
+    // Saved log shown instead of a live stream
+    offline_file: Option<PathBuf>,
+    entries: VecDeque<LogEntry>,
+    // Sequence number of the front entry; entries are numbered so filtered rows survive eviction
+    first_seq: u64,
+    filtered: VecDeque<u64>,
+    filter: LogFilter,
+    package: String,
+    package_checked: Option<Instant>,
+    // A running pidof lookup: device, package and the pids found
+    pid_lookup: Option<Receiver<(String, String, Vec<u32>)>>,
+    // Rows at or past this sequence number are hidden while paused
+    paused_at: Option<u64>,
+    auto_scroll: bool,
+}
+
+impl Default for LogcatTab {
+    fn default() -> Self {
+        Self::new(Arc::new(AdbManager::new()))
+    }
+}
+
+impl LogcatTab {
+    pub fn new(adb_manager: Arc<AdbManager>) -> Self {
+        Self {
+            adb_manager,
+            device_id: None,
+            buffers: vec![LogBuffer::Main, LogBuffer::System, LogBuffer::Crash],
+            stream: None,
+            stream_status: String::new(),
+            recording: None,
+            offline_file: None,
+            entries: VecDeque::new(),
+            first_seq: 0,
+            filtered: VecDeque::new(),
+            filter: LogFilter::default(),
+            package: String::new(),
+            package_checked: None,
+            pid_lookup: None,
+            paused_at: None,
+            auto_scroll: true,
+        }
+    }
+
+    fn next_seq(&self) -> u64 {
+        self.first_seq + self.entries.len() as u64
+    }
+
+    fn push(&mut self, entry: LogEntry) {
+        if self.entries.len() >= MAX_LINES {
+            self.entries.pop_front();
+            self.first_seq += 1;
+            while self.filtered.front().is_some_and(|&seq| seq < self.first_seq) {
+                self.filtered.pop_front();
+            }
+        }
+        if self.filter.matches(&entry) {
+            self.filtered.push_back(self.next_seq());
+        }
+        self.entries.push_back(entry);
+    }
+
+    fn refilter(&mut self) {
+        self.filtered = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| self.filter.matches(entry))
+            .map(|(index, _)| self.first_seq + index as u64)
+            .collect();
+    }
+
+    fn clear(&mut self) {
+        self.first_seq = self.next_seq();
+        self.entries.clear();
+        self.filtered.clear();
+        if self.paused_at.is_some() {
+            self.paused_at = Some(self.first_seq);
+        }
+    }
+
+    fn start(&mut self, device_id: &str, ctx: &egui::Context) {
+        self.stream = None;
+        if self.device_id.as_deref() != Some(device_id) || self.offline_file.take().is_some() {
+            self.clear();
+            self.package_checked = None;
+        }
+        self.device_id = Some(device_id.to_string());
+        match LogcatStream::start(&self.adb_manager, device_id, &self.buffers, ctx.clone()) {
+            Ok(stream) => {
+                self.stream = Some(stream);
+                self.stream_status = format!("Streaming from {}", device_id);
+            }
+            Err(e) => self.stream_status = format!("Failed to start logcat: {}", e),
+        }
+    }
+
+    fn stop(&mut self) {
+        self.stream = None;
+        self.stream_status = "Stopped".to_string();
+    }
+
+    // Called every frame, whichever tab is showing, so the buffer and any recording keep up
+    pub fn update(&mut self) {
+        let Some(stream) = &mut self.stream else {
+            return;
+        };
+        let lines: Vec<String> = stream.try_recv().collect();
+        let exited = stream.exit_message();
+        let received = !lines.is_empty();
+        for line in lines {
+            self.record(&line);
+            self.push(LogEntry::parse(&line));
+        }
+        if received {
+            self.flush_recording();
+        }
+        if let Some(message) = exited {
+            self.stream = None;
+            self.stream_status = message;
+        }
+    }
+
+    fn record(&mut self, line: &str) {
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+        if let Err(e) = writeln!(recording.writer, "{}", line) {
+            self.stream_status = format!("Recording to {} stopped: {}", recording.path.display(), e);
+            self.recording = None;
+            return;
+        }
+        recording.lines += 1;
+    }
+
+    // Keeps the file current while the tab is hidden, not just when recording stops
+    fn flush_recording(&mut self) {
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+        if let Err(e) = recording.writer.flush() {
+            self.stream_status = format!("Recording to {} stopped: {}", recording.path.display(), e);
+            self.recording = None;
+        }
+    }
+
+    fn start_recording(&mut self) -> Option<String> {
+        let path = FileDialog::new()
+            .set_title("Record logcat to file")
+            .set_file_name("logcat.txt")
+            .add_filter("Log", &["txt", "log"])
+            .save_file()?;
+        match File::create(&path) {
+            Ok(file) => {
+                let message = format!("Recording to {}", path.display());
+                self.recording = Some(Recording {
+                    path,
+                    writer: BufWriter::new(file),
+                    lines: 0,
+                });
+                Some(message)
+            }
+            Err(e) => Some(format!("Error: could not create {}: {}", path.display(), e)),
+        }
+    }
+
+    fn stop_recording(&mut self) -> Option<String> {
+        let mut recording = self.recording.take()?;
+        Some(match recording.writer.flush() {
+            Ok(_) => format!("Saved {} lines to {}", recording.lines, recording.path.display()),
+            Err(e) => format!("Error: saving {}: {}", recording.path.display(), e),
+        })
+    }
+
+    fn open_file(&mut self) -> Option<String> {
+        let path = FileDialog::new()
+            .set_title("Open saved logcat")
+            .pick_file()?;
+        let entries = match read_log_file(&path) {
+            Ok(entries) => entries,
+            Err(e) => return Some(format!("Error: could not read {}: {}", path.display(), e)),
+        };
+
+        self.stream = None;
+        self.device_id = None;
+        self.clear();
+        let total = entries.len();
+        for entry in entries {
+            self.push(entry);
+        }
+        self.stream_status = if total > MAX_LINES {
+            format!("Viewing {} (last {} of {} lines)", path.display(), MAX_LINES, total)
+        } else {
+            format!("Viewing {}", path.display())
+        };
+        self.offline_file = Some(path);
+        None
+    }
+
+    // Writes the rows that pass the current filters, as plain threadtime text or JSON lines
+    fn export(&self, json: bool) -> Option<String> {
+        let (name, extension) = if json { ("logcat.jsonl", "jsonl") } else { ("logcat.txt", "txt") };
+        let path = FileDialog::new()
+            .set_title("Export filtered log")
+            .set_file_name(name)
+            .add_filter(extension, &[extension])
+            .save_file()?;
+
+        let result = File::create(&path).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            for &seq in &self.filtered {
+                let entry = &self.entries[(seq - self.first_seq) as usize];
+                if json {
+                    writeln!(writer, "{}", entry.to_json())?;
+                } else {
+                    writeln!(writer, "{}", entry.line)?;
+                }
+            }
+            writer.flush()
+        });
+        Some(match result {
+            Ok(_) => format!("Exported {} lines to {}", self.filtered.len(), path.display()),
+            Err(e) => format!("Error: exporting to {}: {}", path.display(), e),
+        })
+    }
+
+    // The package filter works on process ids, which change whenever the app restarts.
+    // pidof is an adb round trip, so it runs on a worker thread.
+    fn update_package_pids(&mut self, ctx: &egui::Context) {
+        let package = self.package.trim().to_string();
+        let Some(device_id) = self.device_id.clone().filter(|_| !package.is_empty()) else {
+            if self.filter.package_pids.take().is_some() {
+                self.refilter();
+            }
+            return;
+        };
+
+        if let Some((lookup_device, lookup_package, pids)) = self.pid_lookup.as_ref().and_then(|r| r.try_recv().ok()) {
+            self.pid_lookup = None;
+            // The device or package may have changed while the lookup ran
+            let pids = Some(pids);
+            if lookup_device == device_id && lookup_package == package && pids != self.filter.package_pids {
+                self.filter.package_pids = pids;
+                self.refilter();
+            }
+        }
+        if self.pid_lookup.is_some() || self.package_checked.is_some_and(|t| t.elapsed() < PACKAGE_PID_INTERVAL) {
+            return;
+        }
+        self.package_checked = Some(Instant::now());
+
+        let (sender, receiver) = mpsc::channel();
+        let adb_manager = self.adb_manager.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let pids = adb_manager.pidof(&device_id, &package);
+            let _ = sender.send((device_id, package, pids));
+            ctx.request_repaint();
+        });
+        self.pid_lookup = Some(receiver);
+    }
+
+    fn show_filters(&mut self, ui: &mut egui::Ui) {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Level:");
+            egui::ComboBox::from_id_source("logcat_level")
+                .selected_text(self.filter.min_level.label())
+                .show_ui(ui, |ui| {
+                    for level in LogLevel::ALL {
+                        changed |= ui.selectable_value(&mut self.filter.min_level, level, level.label()).changed();
+                    }
+                });
+            ui.label("Tag:");
+            changed |= ui.add(egui::TextEdit::singleline(&mut self.filter.tag).desired_width(100.0)).changed();
+            ui.label("PID:");
+            changed |= ui.add(egui::TextEdit::singleline(&mut self.filter.pid).desired_width(60.0)).changed();
+            ui.label("Package:");
+            if ui.add(egui::TextEdit::singleline(&mut self.package).desired_width(160.0).hint_text("com.example.app")).changed() {
+                self.package_checked = None;
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Search:");
+            let mut search_changed = ui.add(egui::TextEdit::singleline(&mut self.filter.search).desired_width(240.0)).changed();
+            search_changed |= ui.checkbox(&mut self.filter.use_regex, "Regex").changed();
+            search_changed |= ui.checkbox(&mut self.filter.case_sensitive, "Match case").changed();
+            if search_changed {
+                self.filter.compile();
+                changed = true;
+            }
+            if let Some(error) = &self.filter.regex_error {
+                ui.colored_label(egui::Color32::RED, "Invalid regex").on_hover_text(error);
+            }
+        });
+
+        if changed {
+            self.refilter();
+        }
+        if self.filter.package_pids.as_ref().is_some_and(Vec::is_empty) {
+            ui.colored_label(egui::Color32::YELLOW, format!("{} is not running", self.package.trim()));
+        }
+    }
+
+    fn show_log(&self, ui: &mut egui::Ui) {
+        let visible = match self.paused_at {
+            Some(paused_at) => self.filtered.partition_point(|&seq| seq < paused_at),
+            None => self.filtered.len(),
+        };
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+
+        egui::ScrollArea::both()
+            .auto_shrink([false, false])
+            .stick_to_bottom(self.auto_scroll && self.paused_at.is_none())
+            .show_rows(ui, row_height, visible, |ui, rows| {
+                for row in rows {
+                    let entry = &self.entries[(self.filtered[row] - self.first_seq) as usize];
+                    let text = egui::RichText::new(&entry.line).monospace().color(entry.color());
+                    ui.add(egui::Label::new(text).wrap(false));
+                }
+            });
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, context: &mut DeviceContext) -> Option<String> {
+        ui.heading("Logcat");
+
+        let active = context.selection.active().map(str::to_string);
+        // Follow the active device while streaming
+        if let Some(active) = active.as_deref().filter(|a| self.stream.is_some() && self.device_id.as_deref() != Some(*a)) {
+            self.start(active, ui.ctx());
+        }
+        self.update_package_pids(ui.ctx());
+        let mut message = None;
+        if self.filter.package_pids.is_some() {
+            // Keep polling for the package's pids even when no new lines arrive
+            ui.ctx().request_repaint_after(PACKAGE_PID_INTERVAL);
+        }
+
+        ui.horizontal(|ui| {
+            context.device_picker(ui, "logcat_device");
+            let usable = context.active_device().is_some_and(|d| d.is_usable());
+            if self.stream.is_some() {
+                if ui.button("Stop").clicked() {
+                    self.stop();
+                }
+            } else if ui.add_enabled(usable, egui::Button::new("Start")).clicked() {
+                if let Some(active) = &active {
+                    self.start(active, ui.ctx());
+                }
+            }
+            if self.recording.is_some() {
+                if ui.button("Stop recording").clicked() {
+                    message = self.stop_recording();
+                }
+            } else if ui.button("Record…").on_hover_text("Write every line from the live stream to a file").clicked() {
+                message = self.start_recording();
+            }
+            if ui.button("Open log…").on_hover_text("View a saved threadtime log with the same filters").clicked() {
+                message = self.open_file();
+            }
+            ui.menu_button("Export", |ui| {
+                if ui.button("Filtered lines as text…").clicked() {
+                    message = self.export(false);
+                    ui.close_menu();
+                }
+                if ui.button("Filtered lines as JSON lines…").clicked() {
+                    message = self.export(true);
+                    ui.close_menu();
+                }
+            });
+
+            ui.separator();
+            let mut buffers_changed = false;
+            for buffer in LogBuffer::ALL {
+                let mut enabled = self.buffers.contains(&buffer);
+                // At least one buffer has to stay selected
+                let locked = enabled && self.buffers.len() == 1;
+                if ui.add_enabled(!locked, egui::Checkbox::new(&mut enabled, buffer.name())).changed() {
+                    if enabled {
+                        self.buffers.push(buffer);
+                    } else {
+                        self.buffers.retain(|b| *b != buffer);
+                    }
+                    buffers_changed = true;
+                }
+            }
+            if buffers_changed && self.stream.is_some() {
+                if let Some(device_id) = self.device_id.clone() {
+                    self.start(&device_id, ui.ctx());
+                }
+            }
+        });
+
+        self.show_filters(ui);
+
+        ui.horizontal(|ui| {
+            let pause_label = if self.paused_at.is_some() { "Resume" } else { "Pause" };
+            if ui.button(pause_label).clicked() {
+                self.paused_at = match self.paused_at {
+                    Some(_) => None,
+                    None => Some(self.next_seq()),
+                };
+            }
+            ui.checkbox(&mut self.auto_scroll, "Auto-scroll");
+            if ui.button("Clear").clicked() {
+                self.clear();
+            }
+            ui.separator();
+            ui.label(format!("{} of {} lines", self.filtered.len(), self.entries.len()));
+            if let Some(paused_at) = self.paused_at {
+                ui.weak(format!("(paused, {} new)", self.next_seq().saturating_sub(paused_at)));
+            }
+            ui.weak(&self.stream_status);
+            if let Some(recording) = &self.recording {
+                ui.colored_label(egui::Color32::RED, format!("● REC {} lines", recording.lines))
+                    .on_hover_text(recording.path.display().to_string());
+            }
+        });
+
+        ui.separator();
+        self.show_log(ui);
+
+        message
+    }
+}
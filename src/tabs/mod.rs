@@ -1,9 +1,19 @@
 pub mod connect_tab;
+pub mod crashes_tab;
 pub mod devices_tab;
 pub mod reverse_tcp_tab;
 pub mod file_manager_tab;
+pub mod install_tab;
+pub mod logcat_tab;
+pub mod packages_tab;
+pub mod shell_tab;
 
 pub use connect_tab::ConnectTab;
+pub use crashes_tab::CrashesTab;
 pub use devices_tab::DevicesTab;
 pub use reverse_tcp_tab::ReverseTcpTab;
-pub use file_manager_tab::FileManagerTab; 
\ No newline at end of file
+pub use file_manager_tab::FileManagerTab;
+pub use install_tab::InstallTab;
+pub use logcat_tab::LogcatTab;
+pub use packages_tab::PackagesTab;
+pub use shell_tab::ShellTab; 
\ No newline at end of file
@@ -0,0 +1,391 @@
+use eframe::egui;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use crate::adb::{AdbManager, InstalledPackage};
+use crate::device_context::DeviceContext;
+
+const ACTION_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, PartialEq)]
+enum PackageFilter {
+    All,
+    User,
+    System,
+    Disabled,
+}
+
+impl PackageFilter {
+    const ALL: [PackageFilter; 4] = [
+        PackageFilter::All,
+        PackageFilter::User,
+        PackageFilter::System,
+        PackageFilter::Disabled,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            PackageFilter::All => "All",
+            PackageFilter::User => "User",
+            PackageFilter::System => "System",
+            PackageFilter::Disabled => "Disabled",
+        }
+    }
+
+    fn matches(&self, package: &InstalledPackage) -> bool {
+        match self {
+            PackageFilter::All => true,
+            PackageFilter::User => !package.system,
+            PackageFilter::System => package.system,
+            PackageFilter::Disabled => package.disabled,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PackageAction {
+    Launch,
+    ForceStop,
+    ClearData,
+    Enable,
+    Disable,
+    Uninstall { keep_data: bool },
+}
+
+impl PackageAction {
+    fn label(&self) -> &'static str {
+        match self {
+            PackageAction::Launch => "Launch",
+            PackageAction::ForceStop => "Force stop",
+            PackageAction::ClearData => "Clear data",
+            PackageAction::Enable => "Enable",
+            PackageAction::Disable => "Disable",
+            PackageAction::Uninstall { keep_data: false } => "Uninstall",
+            PackageAction::Uninstall { keep_data: true } => "Uninstall (keep data)",
+        }
+    }
+
+    fn command(&self, package: &InstalledPackage) -> String {
+        match self {
+            PackageAction::Launch => format!("monkey -p {} -c android.intent.category.LAUNCHER 1", package.name),
+            PackageAction::ForceStop => format!("am force-stop {}", package.name),
+            PackageAction::ClearData => format!("pm clear {}", package.name),
+            PackageAction::Enable => format!("pm enable {}", package.name),
+            // Plain `pm disable` needs root; disable-user is what the Settings app does
+            PackageAction::Disable => format!("pm disable-user --user 0 {}", package.name),
+            PackageAction::Uninstall { keep_data } => {
+                let keep = if *keep_data { " -k" } else { "" };
+                // System apps can't be removed from the image, only for the current user
+                let user = if package.system { " --user 0" } else { "" };
+                format!("pm uninstall{}{} {}", keep, user, package.name)
+            }
+        }
+    }
+
+    // Actions that delete data or change what is installed ask before running
+    fn needs_confirmation(&self) -> bool {
+        matches!(self, PackageAction::ClearData | PackageAction::Disable | PackageAction::Uninstall { .. })
+    }
+
+    fn changes_list(&self) -> bool {
+        matches!(self, PackageAction::Enable | PackageAction::Disable | PackageAction::Uninstall { .. })
+    }
+}
+
+fn run_action(adb_manager: &AdbManager, device: &str, package: &InstalledPackage, action: PackageAction) -> String {
+    let command = match action {
+        // Start the exact launcher activity when the device can resolve it, monkey otherwise
+        PackageAction::Launch => match resolve_launcher(adb_manager, device, &package.name) {
+            Some(component) => format!("am start -n {}", component),
+            None => action.command(package),
+        },
+        _ => action.command(package),
+    };
+
+    let prefix = format!("{}: {} {}", device, action.label(), package.name);
+    match adb_manager.run_shell(device, &command, ACTION_TIMEOUT) {
+        Ok(output) => {
+            let text = format!("{}{}", output.stdout, output.stderr).trim().to_string();
+            // pm and monkey report some failures with exit code 0
+            let failed = output.exit_code != Some(0)
+                || text.lines().any(|line| {
+                    ["Failure", "Failed", "Error", "Exception"].iter().any(|word| line.starts_with(word))
+                        || line.contains("monkey aborted")
+                });
+            match (failed, text.is_empty()) {
+                (true, _) => format!("{} failed: {}", prefix, text),
+                (false, true) => format!("{} done", prefix),
+                (false, false) => format!("{}: {}", prefix, text.lines().last().unwrap_or_default()),
+            }
+        }
+        Err(e) => format!("{} failed: {}", prefix, e.trim()),
+    }
+}
+
+// "com.example/.MainActivity" for the package's launcher activity
+fn resolve_launcher(adb_manager: &AdbManager, device: &str, package: &str) -> Option<String> {
+    let command = format!("cmd package resolve-activity --brief -c android.intent.category.LAUNCHER {}", package);
+    let output = adb_manager.run_shell(device, &command, ACTION_TIMEOUT).ok()?;
+    let component = output.stdout.lines().last()?.trim().to_string();
+    component.starts_with(&format!("{}/", package)).then_some(component)
+}
+
+pub struct PackagesTab {
+    adb_manager: Arc<AdbManager>,
+    // Device the list was loaded from
+    device_id: Option<String>,
+    packages: Vec<InstalledPackage>,
+    error: Option<String>,
+    loading: Option<Receiver<Result<Vec<InstalledPackage>, String>>>,
+    filter: PackageFilter,
+    search: String,
+    selected: Option<String>,
+    keep_data: bool,
+    pending_action: Option<(InstalledPackage, PackageAction)>,
+    running: Option<Receiver<(PackageAction, String)>>,
+}
+
+impl Default for PackagesTab {
+    fn default() -> Self {
+        Self::new(Arc::new(AdbManager::new()))
+    }
+}
+
+impl PackagesTab {
+    pub fn new(adb_manager: Arc<AdbManager>) -> Self {
+        Self {
+            adb_manager,
+            device_id: None,
+            packages: Vec::new(),
+            error: None,
+            loading: None,
+            filter: PackageFilter::All,
+            search: String::new(),
+            selected: None,
+            keep_data: false,
+            pending_action: None,
+            running: None,
+        }
+    }
+
+    fn refresh(&mut self, device_id: &str, ctx: &egui::Context) {
+        let (sender, receiver) = mpsc::channel();
+        let adb_manager = self.adb_manager.clone();
+        let thread_device = device_id.to_string();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let _ = sender.send(adb_manager.list_packages(&thread_device));
+            ctx.request_repaint();
+        });
+
+        if self.device_id.as_deref() != Some(device_id) {
+            self.packages.clear();
+            self.selected = None;
+        }
+        self.device_id = Some(device_id.to_string());
+        self.error = None;
+        self.loading = Some(receiver);
+    }
+
+    fn request_action(&mut self, package: &InstalledPackage, action: PackageAction, ctx: &egui::Context) {
+        if action.needs_confirmation() {
+            self.pending_action = Some((package.clone(), action));
+        } else {
+            self.start_action(package.clone(), action, ctx);
+        }
+    }
+
+    fn start_action(&mut self, package: InstalledPackage, action: PackageAction, ctx: &egui::Context) {
+        let Some(device_id) = self.device_id.clone() else {
+            return;
+        };
+        let (sender, receiver) = mpsc::channel();
+        let adb_manager = self.adb_manager.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let message = run_action(&adb_manager, &device_id, &package, action);
+            let _ = sender.send((action, message));
+            ctx.request_repaint();
+        });
+        self.running = Some(receiver);
+    }
+
+    fn receive(&mut self, ctx: &egui::Context) -> Option<String> {
+        if let Some(result) = self.loading.as_ref().and_then(|r| r.try_recv().ok()) {
+            self.loading = None;
+            match result {
+                Ok(packages) => self.packages = packages,
+                Err(e) => self.error = Some(e.trim().to_string()),
+            }
+        }
+
+        let (action, message) = self.running.as_ref()?.try_recv().ok()?;
+        self.running = None;
+        if action.changes_list() {
+            if let Some(device_id) = self.device_id.clone() {
+                self.refresh(&device_id, ctx);
+            }
+        }
+        Some(message)
+    }
+
+    fn show_confirmation(&mut self, ctx: &egui::Context) {
+        let Some((package, action)) = self.pending_action.clone() else {
+            return;
+        };
+        let mut close = false;
+
+        egui::Window::new("Confirm")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(format!("{} {} on {}?", action.label(), package.name, self.device_id.as_deref().unwrap_or_default()));
+                match action {
+                    PackageAction::ClearData => {
+                        ui.label("All of the app's files, databases and settings are deleted.");
+                    }
+                    PackageAction::Uninstall { keep_data: false } if package.system => {
+                        ui.label("The system app is removed for user 0 and can be restored with `cmd package install-existing`.");
+                    }
+                    _ => {}
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Yes").clicked() {
+                        self.start_action(package.clone(), action, ctx);
+                        close = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        close = true;
+                    }
+                });
+            });
+
+        if close {
+            self.pending_action = None;
+        }
+    }
+
+    fn show_actions(&mut self, ui: &mut egui::Ui) {
+        let Some(package) = self
+            .selected
+            .as_ref()
+            .and_then(|name| self.packages.iter().find(|p| &p.name == name))
+            .cloned()
+        else {
+            ui.weak("Select a package for actions");
+            return;
+        };
+
+        ui.horizontal(|ui| {
+            ui.strong(&package.name);
+            if self.running.is_some() {
+                ui.spinner();
+            }
+        });
+        ui.add_enabled_ui(self.running.is_none(), |ui| {
+            ui.horizontal(|ui| {
+                let toggle = if package.disabled { PackageAction::Enable } else { PackageAction::Disable };
+                for action in [PackageAction::Launch, PackageAction::ForceStop, PackageAction::ClearData, toggle] {
+                    if ui.button(action.label()).clicked() {
+                        self.request_action(&package, action, ui.ctx());
+                    }
+                }
+                ui.separator();
+                let uninstall = PackageAction::Uninstall { keep_data: self.keep_data };
+                if ui.button("Uninstall").on_hover_text(uninstall.command(&package)).clicked() {
+                    self.request_action(&package, uninstall, ui.ctx());
+                }
+                ui.checkbox(&mut self.keep_data, "Keep data (-k)")
+                    .on_hover_text("Keep the app's data and cache directories after uninstalling");
+            });
+        });
+    }
+
+    // Packages passing both the filter and the search, in list order
+    fn visible(&self) -> Vec<&InstalledPackage> {
+        let search = self.search.to_lowercase();
+        self.packages
+            .iter()
+            .filter(|p| self.filter.matches(p) && p.name.to_lowercase().contains(&search))
+            .collect()
+    }
+
+    fn show_list(&mut self, ui: &mut egui::Ui) {
+        let visible = self.visible();
+
+        let mut clicked = None;
+        egui::ScrollArea::both().auto_shrink([false, false]).show(ui, |ui| {
+            egui::Grid::new("package_list").num_columns(4).striped(true).show(ui, |ui| {
+                ui.strong("Package");
+                ui.strong("Version code");
+                ui.strong("UID");
+                ui.strong("Path");
+                ui.end_row();
+
+                for package in &visible {
+                    let mut name = egui::RichText::new(&package.name);
+                    if package.disabled {
+                        name = name.weak().strikethrough();
+                    }
+                    let selected = self.selected.as_deref() == Some(package.name.as_str());
+                    let response = ui.selectable_label(selected, name);
+                    let response = if package.system { response.on_hover_text("System app") } else { response };
+                    if response.clicked() {
+                        clicked = Some(package.name.clone());
+                    }
+                    ui.label(package.version_code.map(|v| v.to_string()).unwrap_or_default());
+                    ui.label(package.uid.map(|u| u.to_string()).unwrap_or_default());
+                    ui.monospace(&package.path);
+                    ui.end_row();
+                }
+            });
+        });
+        if clicked.is_some() {
+            self.selected = clicked;
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, context: &mut DeviceContext) -> Option<String> {
+        ui.heading("Packages");
+        let message = self.receive(ui.ctx());
+        self.show_confirmation(ui.ctx());
+
+        // Load the list for whichever device becomes active
+        let active = context.active_device().filter(|d| d.is_usable()).map(|d| d.id.clone());
+        if let Some(active) = active.as_deref().filter(|a| self.device_id.as_deref() != Some(*a)) {
+            self.refresh(active, ui.ctx());
+        }
+
+        ui.horizontal(|ui| {
+            context.device_picker(ui, "packages_device");
+            let can_refresh = active.is_some() && self.loading.is_none();
+            if ui.add_enabled(can_refresh, egui::Button::new("Refresh")).clicked() {
+                if let Some(active) = &active {
+                    self.refresh(active, ui.ctx());
+                }
+            }
+            if self.loading.is_some() {
+                ui.spinner();
+            }
+        });
+        ui.horizontal(|ui| {
+            for filter in PackageFilter::ALL {
+                ui.selectable_value(&mut self.filter, filter, filter.label());
+            }
+            ui.separator();
+            ui.add(egui::TextEdit::singleline(&mut self.search).desired_width(200.0).hint_text("Filter by name"));
+            ui.weak(format!("{} package(s)", self.visible().len()));
+        });
+
+        if let Some(error) = &self.error {
+            ui.colored_label(egui::Color32::RED, format!("Could not list packages: {}", error));
+        }
+        ui.separator();
+        self.show_actions(ui);
+        ui.separator();
+        self.show_list(ui);
+        message
+    }
+}
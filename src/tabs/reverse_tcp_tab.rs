@@ -1,100 +1,1050 @@
 use eframe::egui;
-use std::process::Command;
-use super::devices_tab::Device;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::adb::{AdbManager, Device, DeviceState};
+use crate::device_context::DeviceContext;
+use serde::{Deserialize, Serialize};
+use crate::port_rules::{is_host_port_listening, socket_spec_editor, Direction, PortPreset, PortRule, SocketKind, SocketSpec};
+use crate::traffic_proxy::{format_bytes, TrafficProxy};
+use crate::watchdog::utc_timestamp;
+
+const SETTINGS_KEY: &str = "port_rules";
+const MAX_REAPPLY_LOG_ENTRIES: usize = 200;
+const MAX_STATUS_MESSAGES: usize = 100;
+const MAX_OUTCOMES: usize = 200;
+// How long a host listener probe result is reused before probing the port again
+const LISTENER_PROBE_INTERVAL: Duration = Duration::from_secs(2);
+// How long a new device may wait for its hardware serial before its rules are looked up by id
+const SERIAL_WAIT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+struct PortSettings {
+    rules: Vec<PortRule>,
+    presets: Vec<PortPreset>,
+    reapply_on_reconnect: bool,
+    // Rules applied to each device, keyed by DeviceLabels::key
+    desired_rules: HashMap<String, Vec<PortRule>>,
+}
+
+impl Default for PortSettings {
+    fn default() -> Self {
+        Self {
+            rules: vec![PortRule::tcp(Direction::Reverse, 8081, 8081)],
+            presets: PortPreset::defaults(),
+            reapply_on_reconnect: true,
+            desired_rules: HashMap::new(),
+        }
+    }
+}
+
+// Mappings currently active on one device, as reported by adb
+struct ActiveMappings {
+    // Each reverse rule with whether its host TCP port has a listener (None for non-TCP sockets)
+    reverse: Vec<(PortRule, Option<bool>)>,
+    forward: Vec<PortRule>,
+}
+
+// What happened when one rule was applied to one device
+struct RuleOutcome {
+    device_id: String,
+    rule: PortRule,
+    // adb's output on success, its error message on failure
+    result: Result<String, String>,
+    // Whether the rule showed up in the device's mapping list afterwards
+    verified: Option<Result<bool, String>>,
+}
+
+impl RuleOutcome {
+    // The rule as adb set it up, with the port adb picked for a host tcp:0 forward
+    fn applied_rule(&self) -> PortRule {
+        let mut rule = self.rule.clone();
+        let assigned = self.result.as_ref().ok().and_then(|output| output.parse::<u16>().ok());
+        if let Some(port) = assigned.filter(|_| rule.direction == Direction::Forward && rule.host.port() == Some(0)) {
+            rule.host = SocketSpec::tcp(port);
+        }
+        rule
+    }
+}
+
+// Results of rule work done on worker threads
+enum RuleEvent {
+    // Outcomes of auto-applied presets are not remembered for re-applying; the preset covers them
+    Outcome { outcome: RuleOutcome, remember: bool },
+    Verified { device_id: String, rule: PortRule, verified: Result<bool, String> },
+    Reapplied(String),
+    Status(String),
+}
+
+fn apply_and_verify(adb_manager: &AdbManager, device_id: &str, rule: &PortRule) -> RuleOutcome {
+    let result = rule.validate().and_then(|_| adb_manager.apply_rule(device_id, rule));
+    let mut outcome = RuleOutcome {
+        device_id: device_id.to_string(),
+        rule: rule.clone(),
+        result,
+        verified: None,
+    };
+    if outcome.result.is_ok() {
+        outcome.verified = Some(adb_manager.verify_rule(device_id, &outcome.applied_rule()));
+    }
+    outcome
+}
+
+// A reverse rule temporarily pointed at a counting proxy instead of the real host port
+struct TrafficMonitor {
+    adb_manager: Arc<AdbManager>,
+    device_id: String,
+    rule: PortRule,
+    proxy: TrafficProxy,
+    restored: bool,
+}
+
+impl TrafficMonitor {
+    // Points the rule back at the real host port
+    fn restore(&mut self) -> Result<String, String> {
+        self.restored = true;
+        self.adb_manager.apply_rule(&self.device_id, &restore_rule(&self.rule))
+    }
+}
+
+// The proxied mapping still holds the device socket, so --no-rebind would make adb refuse
+fn restore_rule(rule: &PortRule) -> PortRule {
+    let mut restored = rule.clone();
+    restored.no_rebind = false;
+    restored
+}
+
+// Runs before the proxy field drops, so the device never keeps a rule to a dead proxy
+impl Drop for TrafficMonitor {
+    fn drop(&mut self) {
+        if !self.restored {
+            let _ = self.restore();
+        }
+    }
+}
 
 pub struct ReverseTcpTab {
-    port: String,
+    adb_manager: Arc<AdbManager>,
+    rules: Vec<PortRule>,
+    presets: Vec<PortPreset>,
+    selected_preset: usize,
+    new_preset_name: String,
+    reapply_on_reconnect: bool,
+    desired_rules: HashMap<String, Vec<PortRule>>,
+    reapply_log: VecDeque<String>,
     devices: Vec<Device>,
+    // Device id -> DeviceLabels::key, refreshed from the shared context every frame
+    device_keys: HashMap<String, String>,
+    // Newly connected devices whose hardware serial is not known yet, and since when
+    awaiting_serial: Vec<(Device, Instant)>,
+    active_mappings: BTreeMap<String, Result<ActiveMappings, String>>,
+    outcomes: Vec<RuleOutcome>,
+    listener_probes: HashMap<u16, (Instant, bool)>,
+    monitors: Vec<TrafficMonitor>,
+    // (device id, device socket) -> host port adb picked for a tcp:0 forward
+    assigned_ports: HashMap<(String, String), u16>,
     status_messages: Vec<String>,
+    events: Receiver<RuleEvent>,
+    event_sender: Sender<RuleEvent>,
 }
 
 impl Default for ReverseTcpTab {
     fn default() -> Self {
+        Self::new(Arc::new(AdbManager::new()))
+    }
+}
+
+impl ReverseTcpTab {
+    pub fn new(adb_manager: Arc<AdbManager>) -> Self {
+        let settings = PortSettings::default();
+        let (event_sender, events) = mpsc::channel();
         Self {
-            port: String::from("8081"),
+            adb_manager,
+            rules: settings.rules,
+            presets: settings.presets,
+            selected_preset: 0,
+            new_preset_name: String::new(),
+            reapply_on_reconnect: settings.reapply_on_reconnect,
+            desired_rules: settings.desired_rules,
+            reapply_log: VecDeque::new(),
             devices: Vec::new(),
+            device_keys: HashMap::new(),
+            awaiting_serial: Vec::new(),
+            active_mappings: BTreeMap::new(),
+            outcomes: Vec::new(),
+            listener_probes: HashMap::new(),
+            monitors: Vec::new(),
+            assigned_ports: HashMap::new(),
             status_messages: Vec::new(),
+            events,
+            event_sender,
         }
     }
-}
 
-impl ReverseTcpTab {
-    pub fn refresh_devices(&mut self) {
-        self.devices.clear();
-        
-        if let Ok(output) = Command::new("adb").args(["devices"]).output() {
-            let output_str = String::from_utf8_lossy(&output.stdout);
-            for line in output_str.lines().skip(1) {
-                if !line.trim().is_empty() {
-                    let parts: Vec<&str> = line.split_whitespace().collect();
-                    if parts.len() >= 2 {
-                        self.devices.push(Device {
-                            id: parts[0].to_string(),
-                            status: parts[1].to_string(),
-                        });
+    pub fn load(&mut self, storage: &dyn eframe::Storage) {
+        if let Some(settings) = eframe::get_value::<PortSettings>(storage, SETTINGS_KEY) {
+            self.rules = settings.rules;
+            self.presets = settings.presets;
+            self.reapply_on_reconnect = settings.reapply_on_reconnect;
+            self.desired_rules = settings.desired_rules;
+        }
+    }
+
+    pub fn save(&self, storage: &mut dyn eframe::Storage) {
+        let settings = PortSettings {
+            rules: self.rules.clone(),
+            presets: self.presets.clone(),
+            reapply_on_reconnect: self.reapply_on_reconnect,
+            desired_rules: self.desired_rules.clone(),
+        };
+        eframe::set_value(storage, SETTINGS_KEY, &settings);
+    }
+
+    fn sync_devices(&mut self, context: &DeviceContext) {
+        self.devices = context.devices.clone();
+        self.device_keys = self
+            .devices
+            .iter()
+            .map(|d| (d.id.clone(), context.labels.key(&d.id)))
+            .collect();
+        // Rules remembered while only the device id was known move to the serial key
+        for (device_id, key) in &self.device_keys {
+            if device_id != key {
+                if let Some(rules) = self.desired_rules.remove(device_id) {
+                    self.desired_rules.entry(key.clone()).or_insert(rules);
+                }
+            }
+        }
+    }
+
+    fn device_key(&self, device_id: &str) -> String {
+        self.device_keys.get(device_id).cloned().unwrap_or_else(|| device_id.to_string())
+    }
+
+    fn remember_rule(&mut self, device_id: &str, rule: &PortRule) {
+        let rules = self.desired_rules.entry(self.device_key(device_id)).or_default();
+        rules.retain(|r| r.direction != rule.direction || r.listener() != rule.listener());
+        rules.push(rule.clone());
+    }
+
+    fn forget_rule(&mut self, device_id: &str, rule: &PortRule) {
+        let key = self.device_key(device_id);
+        if let Some(rules) = self.desired_rules.get_mut(&key) {
+            rules.retain(|r| r.direction != rule.direction || r.listener() != rule.listener());
+            if rules.is_empty() {
+                self.desired_rules.remove(&key);
+            }
+        }
+    }
+
+    fn push_status(&mut self, message: String) {
+        if self.status_messages.len() >= MAX_STATUS_MESSAGES {
+            self.status_messages.remove(0);
+        }
+        self.status_messages.push(message);
+    }
+
+    fn log_reapply(&mut self, message: String) {
+        if self.reapply_log.len() >= MAX_REAPPLY_LOG_ENTRIES {
+            self.reapply_log.pop_front();
+        }
+        self.reapply_log.push_back(format!("[{}] {}", utc_timestamp(), message));
+    }
+
+    fn receive_events(&mut self) {
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                RuleEvent::Outcome { outcome, remember } => {
+                    if remember && outcome.result.is_ok() {
+                        self.remember_rule(&outcome.device_id, &outcome.rule);
+                    }
+                    self.record_assigned_port(&outcome);
+                    if self.outcomes.len() >= MAX_OUTCOMES {
+                        self.outcomes.remove(0);
+                    }
+                    self.outcomes.push(outcome);
+                }
+                RuleEvent::Verified { device_id, rule, verified } => {
+                    for outcome in self.outcomes.iter_mut().filter(|o| o.device_id == device_id && o.applied_rule() == rule) {
+                        outcome.verified = Some(verified.clone());
                     }
                 }
+                RuleEvent::Reapplied(message) => self.log_reapply(message),
+                RuleEvent::Status(message) => self.push_status(message),
             }
         }
     }
 
-    pub fn setup_reverse_tcp_all(&mut self) {
-        self.status_messages.clear();
-        
-        for device in &self.devices {
-            match Command::new("adb")
-                .args(["-s", &device.id, "reverse", &format!("tcp:{}", self.port), &format!("tcp:{}", self.port)])
-                .output()
-            {
-                Ok(output) => {
-                    let message = format!(
-                        "Device {}: {}", 
-                        device.id, 
-                        String::from_utf8_lossy(&output.stdout).trim()
-                    );
-                    self.status_messages.push(message);
+    // Called every frame with the devices that just connected, whichever tab is showing.
+    // Presets and remembered rules are applied on a worker thread per device; at startup every
+    // attached device counts as newly connected. Remembered rules are keyed by the hardware
+    // serial, so a device waits until the tracker has read it, or for SERIAL_WAIT at most.
+    pub fn handle_connected(&mut self, devices: &[Device], context: &DeviceContext, ctx: &egui::Context) {
+        self.receive_events();
+        for device in devices {
+            if !self.awaiting_serial.iter().any(|(d, _)| d.id == device.id) {
+                self.awaiting_serial.push((device.clone(), Instant::now()));
+            }
+        }
+        if self.awaiting_serial.is_empty() {
+            return;
+        }
+        self.sync_devices(context);
+
+        let mut ready = Vec::new();
+        self.awaiting_serial.retain(|(device, since)| {
+            if !context.devices.iter().any(|d| d.id == device.id) {
+                return false;
+            }
+            if context.labels.hardware_serial(&device.id).is_some() || since.elapsed() >= SERIAL_WAIT {
+                ready.push(device.clone());
+                return false;
+            }
+            true
+        });
+        if !self.awaiting_serial.is_empty() {
+            ctx.request_repaint_after(Duration::from_secs(1));
+        }
+
+        let presets: Vec<PortPreset> = self.presets.iter().filter(|p| p.auto_apply).cloned().collect();
+        for device in &ready {
+            // Rules do not survive a reboot or replug, so restore what was set up before,
+            // except what an auto-applied preset sets up anyway
+            let desired: Vec<PortRule> = match self.desired_rules.get(&self.device_key(&device.id)) {
+                Some(rules) if self.reapply_on_reconnect => rules
+                    .iter()
+                    .filter(|rule| {
+                        !presets.iter().flat_map(|p| &p.rules).any(|p| p.direction == rule.direction && p.listener() == rule.listener())
+                    })
+                    // A monitored rule points at the proxy; re-applying would bypass it
+                    .filter(|rule| {
+                        !self.monitors.iter().any(|m| m.device_id == device.id && m.rule.device == rule.device)
+                    })
+                    .cloned()
+                    .collect(),
+                _ => Vec::new(),
+            };
+            // Outcomes from this device's previous connection are stale now
+            self.outcomes.retain(|o| o.device_id != device.id);
+            if presets.is_empty() && desired.is_empty() {
+                continue;
+            }
+
+            let adb_manager = self.adb_manager.clone();
+            let sender = self.event_sender.clone();
+            let device_id = device.id.clone();
+            let presets = presets.clone();
+            let ctx = ctx.clone();
+            thread::spawn(move || {
+                for preset in &presets {
+                    let _ = sender.send(RuleEvent::Status(format!("Device {}: auto-applying preset \"{}\"", device_id, preset.name)));
+                    for rule in &preset.rules {
+                        let outcome = apply_and_verify(&adb_manager, &device_id, rule);
+                        let _ = sender.send(RuleEvent::Outcome { outcome, remember: false });
+                    }
+                    ctx.request_repaint();
+                }
+
+                if !desired.is_empty() {
+                    let failures = desired
+                        .iter()
+                        .filter(|rule| adb_manager.apply_rule(&device_id, rule).is_err())
+                        .count();
+                    let message = if failures == 0 {
+                        format!("Device {}: re-applied {} rule(s)", device_id, desired.len())
+                    } else {
+                        format!("Device {}: re-applied {} rule(s), {} failed", device_id, desired.len() - failures, failures)
+                    };
+                    let _ = sender.send(RuleEvent::Reapplied(message));
+                    ctx.request_repaint();
                 }
+            });
+        }
+    }
+
+    fn refresh_mappings(&mut self) {
+        self.active_mappings.clear();
+        for device in self.devices.iter().filter(|d| d.is_usable()) {
+            let mappings = self.adb_manager.list_reverse(&device.id).and_then(|reverse| {
+                let forward = self.adb_manager.list_forward(&device.id)?;
+                let reverse = reverse
+                    .into_iter()
+                    .map(|rule| {
+                        let listening = rule.host.port().map(is_host_port_listening);
+                        (rule, listening)
+                    })
+                    .collect();
+                Ok(ActiveMappings { reverse, forward })
+            });
+            self.active_mappings.insert(device.id.clone(), mappings);
+        }
+    }
+
+    fn show_active_mappings(&mut self, ui: &mut egui::Ui, context: &DeviceContext) {
+        if ui.button("Refresh mappings").clicked() {
+            self.refresh_mappings();
+        }
+        if self.active_mappings.is_empty() {
+            ui.label("Press refresh to load the mappings of every usable device");
+            return;
+        }
+
+        let mut removed = None;
+        for (device_id, mappings) in &self.active_mappings {
+            let name = context.labels.rich_text(device_id, context.labels.display_name(device_id));
+            ui.label(name.strong());
+            let mappings = match mappings {
+                Ok(mappings) => mappings,
                 Err(e) => {
-                    let error_message = format!("Error for device {}: {}", device.id, e);
-                    self.status_messages.push(error_message);
+                    ui.colored_label(egui::Color32::RED, format!("Failed to list mappings: {}", e.trim()));
+                    continue;
+                }
+            };
+            if mappings.reverse.is_empty() && mappings.forward.is_empty() {
+                ui.weak("No reverse or forward mappings");
+                continue;
+            }
+
+            egui::Grid::new(("active_mappings", device_id)).num_columns(5).striped(true).show(ui, |ui| {
+                ui.strong("Type");
+                ui.strong("Device");
+                ui.strong("Host");
+                ui.strong("Host listener");
+                ui.label("");
+                ui.end_row();
+
+                for (rule, listening) in &mappings.reverse {
+                    ui.label("reverse");
+                    ui.label(rule.device.to_string());
+                    ui.label(rule.host.to_string());
+                    match listening {
+                        Some(true) => ui.colored_label(egui::Color32::GREEN, "listening"),
+                        Some(false) => ui.colored_label(egui::Color32::RED, "nothing listening")
+                            .on_hover_text("The device can connect, but nothing on this computer accepts the connection"),
+                        None => ui.weak("-"),
+                    };
+                    if ui.small_button("Remove").clicked() {
+                        removed = Some((device_id.clone(), rule.clone()));
+                    }
+                    ui.end_row();
+                }
+                for rule in &mappings.forward {
+                    ui.label("forward");
+                    ui.label(rule.device.to_string());
+                    let assigned = self.assigned_ports.get(&(device_id.clone(), rule.device.to_string()));
+                    if assigned.is_some_and(|port| rule.host.port() == Some(*port)) {
+                        ui.label(format!("{} (assigned by adb)", rule.host))
+                            .on_hover_text("The rule asked for tcp:0, so adb picked this free host port");
+                    } else {
+                        ui.label(rule.host.to_string());
+                    }
+                    ui.weak("-");
+                    if ui.small_button("Remove").clicked() {
+                        removed = Some((device_id.clone(), rule.clone()));
+                    }
+                    ui.end_row();
+                }
+            });
+        }
+
+        if let Some((device_id, rule)) = removed {
+            self.forget_rule(&device_id, &rule);
+            let message = match self.adb_manager.remove_rule(&device_id, &rule) {
+                Ok(_) => format!("Device {}: removed {}", device_id, rule),
+                Err(e) => format!("Error for device {}: removing {}: {}", device_id, rule, e.trim()),
+            };
+            self.status_messages = vec![message];
+            self.refresh_mappings();
+        }
+    }
+
+    // Reverse rules do not survive an offline device, so reconnect and set them up again.
+    // Reconnecting can wait up to CONNECT_TIMEOUT, so it all runs on a worker thread.
+    fn recover_device(&mut self, device: &Device, ctx: &egui::Context) {
+        self.status_messages.clear();
+        let adb_manager = self.adb_manager.clone();
+        let sender = self.event_sender.clone();
+        let device = device.clone();
+        let rules = self.rules.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            if let Err(e) = adb_manager.reconnect_offline(&device) {
+                let message = format!("Error for device {}: reconnect failed: {}", device.id, e.trim());
+                let _ = sender.send(RuleEvent::Status(message));
+                ctx.request_repaint();
+                return;
+            }
+            let devices = adb_manager.list_devices().unwrap_or_default();
+            if devices.iter().any(|d| d.id == device.id && d.is_usable()) {
+                for rule in &rules {
+                    let outcome = apply_and_verify(&adb_manager, &device.id, rule);
+                    let _ = sender.send(RuleEvent::Outcome { outcome, remember: true });
+                    ctx.request_repaint();
+                }
+            } else {
+                let message = format!("Device {}: still not available after reconnecting", device.id);
+                let _ = sender.send(RuleEvent::Status(message));
+            }
+            ctx.request_repaint();
+        });
+    }
+
+    fn for_each_usable(&mut self, devices: Vec<Device>, mut action: impl FnMut(&mut Self, &Device)) {
+        self.status_messages.clear();
+        self.outcomes.clear();
+
+        for device in devices {
+            if device.is_usable() {
+                action(self, &device);
+            } else {
+                self.push_status(format!("Device {}: skipped ({})", device.id, device.state));
+            }
+        }
+    }
+
+    fn apply_rules(&mut self, device: &Device, ctx: &egui::Context) {
+        self.apply_rule_list(device, self.rules.clone(), ctx);
+    }
+
+    fn apply_selected_preset(&mut self, device: &Device, ctx: &egui::Context) {
+        if let Some(preset) = self.presets.get(self.selected_preset) {
+            self.apply_rule_list(device, preset.rules.clone(), ctx);
+        }
+    }
+
+    // Every apply and verify is an adb round trip, so they run on a worker thread per device
+    fn apply_rule_list(&mut self, device: &Device, rules: Vec<PortRule>, ctx: &egui::Context) {
+        let adb_manager = self.adb_manager.clone();
+        let sender = self.event_sender.clone();
+        let device_id = device.id.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            for rule in &rules {
+                let outcome = apply_and_verify(&adb_manager, &device_id, rule);
+                let _ = sender.send(RuleEvent::Outcome { outcome, remember: true });
+                ctx.request_repaint();
+            }
+        });
+    }
+
+    fn record_assigned_port(&mut self, outcome: &RuleOutcome) {
+        if outcome.rule.host.port() != Some(0) {
+            return;
+        }
+        if let Some(port) = outcome.applied_rule().host.port().filter(|p| *p != 0) {
+            self.assigned_ports.insert((outcome.device_id.clone(), outcome.rule.device.to_string()), port);
+        }
+    }
+
+    fn verify_outcomes(&self, ctx: &egui::Context) {
+        let checks: Vec<(String, PortRule)> = self
+            .outcomes
+            .iter()
+            .filter(|o| o.result.is_ok())
+            .map(|o| (o.device_id.clone(), o.applied_rule()))
+            .collect();
+        let adb_manager = self.adb_manager.clone();
+        let sender = self.event_sender.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            for (device_id, rule) in checks {
+                let verified = adb_manager.verify_rule(&device_id, &rule);
+                let _ = sender.send(RuleEvent::Verified { device_id, rule, verified });
+            }
+            ctx.request_repaint();
+        });
+    }
+
+    fn show_outcomes(&mut self, ui: &mut egui::Ui, context: &DeviceContext) {
+        egui::Grid::new("rule_outcomes").num_columns(4).striped(true).show(ui, |ui| {
+            ui.strong("Device");
+            ui.strong("Rule");
+            ui.strong("Result");
+            ui.strong("Listed by adb");
+            ui.end_row();
+
+            for outcome in &self.outcomes {
+                ui.label(context.labels.rich_text(&outcome.device_id, context.labels.display_name(&outcome.device_id)));
+                ui.label(outcome.applied_rule().to_string());
+                match &outcome.result {
+                    Ok(_) => ui.colored_label(egui::Color32::GREEN, "OK"),
+                    Err(e) => ui.colored_label(egui::Color32::RED, format!("Failed: {}", e.trim())),
+                };
+                match &outcome.verified {
+                    Some(Ok(true)) => ui.colored_label(egui::Color32::GREEN, "yes"),
+                    Some(Ok(false)) => ui.colored_label(egui::Color32::RED, "missing")
+                        .on_hover_text("adb accepted the rule but does not list it; try applying again"),
+                    Some(Err(e)) => ui.colored_label(egui::Color32::YELLOW, "unknown").on_hover_text(e.trim()),
+                    None => ui.weak("-"),
+                };
+                ui.end_row();
+            }
+        });
+
+        if ui.button("Verify again").clicked() {
+            self.verify_outcomes(ui.ctx());
+        }
+    }
+
+    fn remove_rules(&mut self, device: &Device) {
+        for rule in self.rules.clone() {
+            self.forget_rule(&device.id, &rule);
+            // adb lists a tcp:0 forward under the port it picked, so remove that one
+            let mut live = rule.clone();
+            let assigned_key = (device.id.clone(), rule.device.to_string());
+            if rule.direction == Direction::Forward && rule.host.port() == Some(0) {
+                if let Some(port) = self.assigned_ports.get(&assigned_key) {
+                    live.host = SocketSpec::tcp(*port);
+                }
+            }
+            let message = match self.adb_manager.remove_rule(&device.id, &live) {
+                Ok(_) => {
+                    self.assigned_ports.remove(&assigned_key);
+                    format!("Device {}: removed {}", device.id, live)
+                }
+                Err(e) => format!("Error for device {}: removing {}: {}", device.id, live, e.trim()),
+            };
+            self.push_status(message);
+        }
+    }
+
+    fn remove_all_rules(&mut self, device: &Device) {
+        let key = self.device_key(&device.id);
+        self.desired_rules.remove(&key);
+        self.assigned_ports.retain(|(device_id, _), _| *device_id != device.id);
+        for (direction, result) in [
+            (Direction::Reverse, self.adb_manager.reverse_remove_all(&device.id)),
+            (Direction::Forward, self.adb_manager.forward_remove_all(&device.id)),
+        ] {
+            let message = match result {
+                Ok(_) => format!("Device {}: removed all {} rules", device.id, direction.label()),
+                Err(e) => format!("Error for device {}: {}", device.id, e.trim()),
+            };
+            self.push_status(message);
+        }
+    }
+
+    fn probe_listener(&mut self, port: u16) -> bool {
+        match self.listener_probes.get(&port) {
+            Some((checked, listening)) if checked.elapsed() < LISTENER_PROBE_INTERVAL => *listening,
+            _ => {
+                let listening = is_host_port_listening(port);
+                self.listener_probes.insert(port, (Instant::now(), listening));
+                listening
+            }
+        }
+    }
+
+    fn start_monitor(&mut self, device: &Device, rule: &PortRule) {
+        let Some(target_port) = rule.host.port() else {
+            return;
+        };
+        if self.monitors.iter().any(|m| m.device_id == device.id && m.rule.device == rule.device) {
+            self.push_status(format!("Device {}: {} is already monitored", device.id, rule.device));
+            return;
+        }
+
+        let proxy = match TrafficProxy::start(target_port) {
+            Ok(proxy) => proxy,
+            Err(e) => {
+                self.push_status(format!("Error for device {}: could not start proxy: {}", device.id, e));
+                return;
+            }
+        };
+        let mut proxied = rule.clone();
+        proxied.host = SocketSpec::tcp(proxy.listen_port());
+        proxied.no_rebind = false;
+        match self.adb_manager.apply_rule(&device.id, &proxied) {
+            Ok(_) => {
+                self.push_status(format!(
+                    "Device {}: monitoring {} through local port {}",
+                    device.id, rule, proxy.listen_port()
+                ));
+                self.monitors.push(TrafficMonitor {
+                    adb_manager: self.adb_manager.clone(),
+                    device_id: device.id.clone(),
+                    rule: rule.clone(),
+                    proxy,
+                    restored: false,
+                });
+            }
+            Err(e) => self.push_status(format!("Error for device {}: {}: {}", device.id, proxied, e.trim())),
+        }
+    }
+
+    fn stop_monitor(&mut self, index: usize) {
+        let mut monitor = self.monitors.remove(index);
+        let message = match monitor.restore() {
+            Ok(_) => format!("Device {}: stopped monitoring, restored {}", monitor.device_id, monitor.rule),
+            Err(e) => format!("Error for device {}: restoring {}: {}", monitor.device_id, monitor.rule, e.trim()),
+        };
+        self.status_messages = vec![message];
+    }
+
+    // Called on exit, restoring every monitored rule; returns the restores that failed
+    pub fn stop_monitors(&mut self) -> Vec<String> {
+        self.monitors
+            .drain(..)
+            .filter_map(|mut monitor| {
+                monitor.restore().err().map(|e| {
+                    format!("Error for device {}: restoring {}: {}", monitor.device_id, monitor.rule, e.trim())
+                })
+            })
+            .collect()
+    }
+
+    fn show_traffic_monitor(&mut self, ui: &mut egui::Ui, context: &DeviceContext) {
+        ui.label("Routes a reverse rule through a local proxy that counts connections and bytes for each device.");
+        let monitorable: Vec<PortRule> = self
+            .rules
+            .iter()
+            .filter(|r| r.direction == Direction::Reverse && r.host.port().is_some_and(|p| p != 0) && r.validate().is_ok())
+            .cloned()
+            .collect();
+        if monitorable.is_empty() {
+            ui.weak("Add a reverse rule with a tcp host socket to monitor it");
+        }
+        for rule in monitorable {
+            ui.horizontal(|ui| {
+                ui.label(rule.to_string());
+                if ui.button("Monitor on Selected").clicked() {
+                    self.for_each_usable(context.target_devices(), |tab, device| tab.start_monitor(device, &rule));
+                }
+            });
+        }
+
+        if self.monitors.is_empty() {
+            return;
+        }
+        // Keep the counters moving while traffic flows
+        ui.ctx().request_repaint_after(Duration::from_millis(500));
+
+        let mut stopped = None;
+        egui::Grid::new("traffic_monitors").num_columns(7).striped(true).show(ui, |ui| {
+            ui.strong("Device");
+            ui.strong("Rule");
+            ui.strong("Connections");
+            ui.strong("Refused");
+            ui.strong("Device → host");
+            ui.strong("Host → device");
+            ui.label("");
+            ui.end_row();
+
+            for (index, monitor) in self.monitors.iter().enumerate() {
+                let stats = monitor.proxy.stats();
+                ui.label(context.labels.rich_text(&monitor.device_id, context.labels.display_name(&monitor.device_id)));
+                ui.label(format!("{} → proxy {} → tcp:{}", monitor.rule.device, monitor.proxy.listen_port(), monitor.proxy.target_port()));
+                ui.label(format!("{} ({} open)", stats.connections, stats.active));
+                if stats.refused > 0 {
+                    ui.colored_label(egui::Color32::RED, stats.refused.to_string())
+                        .on_hover_text("The device connected, but nothing on this computer accepted the connection");
+                } else {
+                    ui.label("0");
                 }
+                ui.label(format_bytes(stats.bytes_to_host));
+                ui.label(format_bytes(stats.bytes_to_device));
+                if ui.small_button("Stop").clicked() {
+                    stopped = Some(index);
+                }
+                ui.end_row();
             }
+        });
+        if let Some(index) = stopped {
+            self.stop_monitor(index);
         }
     }
 
-    pub fn show(&mut self, ui: &mut egui::Ui) -> Option<String> {
-        ui.heading("Reverse TCP Setup");
-        
+    fn show_presets(&mut self, ui: &mut egui::Ui, context: &DeviceContext) {
+        if self.selected_preset >= self.presets.len() {
+            self.selected_preset = 0;
+        }
+
         ui.horizontal(|ui| {
-            ui.label("Port:");
-            ui.text_edit_singleline(&mut self.port);
+            ui.label("Preset:");
+            let selected_name = self.presets.get(self.selected_preset).map_or("None", |p| p.name.as_str());
+            egui::ComboBox::from_id_source("port_preset")
+                .selected_text(selected_name)
+                .show_ui(ui, |ui| {
+                    for (index, preset) in self.presets.iter().enumerate() {
+                        ui.selectable_value(&mut self.selected_preset, index, &preset.name);
+                    }
+                });
+
+            if let Some(preset) = self.presets.get_mut(self.selected_preset) {
+                ui.checkbox(&mut preset.auto_apply, "Auto-apply on connect")
+                    .on_hover_text("Apply this preset to every device as soon as it connects");
+            }
         });
 
+        if let Some(preset) = self.presets.get(self.selected_preset) {
+            for rule in &preset.rules {
+                ui.weak(format!("  {}", rule));
+            }
+        }
+
         ui.horizontal(|ui| {
-            if ui.button("Refresh Devices").clicked() {
-                self.refresh_devices();
+            let has_preset = self.selected_preset < self.presets.len();
+            if ui.add_enabled(has_preset, egui::Button::new("Apply to Selected")).clicked() {
+                let ctx = ui.ctx().clone();
+                self.for_each_usable(context.target_devices(), |tab, device| tab.apply_selected_preset(device, &ctx));
+            }
+            if ui.add_enabled(has_preset, egui::Button::new("Load into editor")).clicked() {
+                self.rules = self.presets[self.selected_preset].rules.clone();
             }
-            if ui.button("Setup Reverse TCP for All").clicked() {
-                self.setup_reverse_tcp_all();
+            if ui.add_enabled(has_preset, egui::Button::new("Update from editor")).clicked() {
+                self.presets[self.selected_preset].rules = self.rules.clone();
+            }
+            if ui.add_enabled(has_preset, egui::Button::new("Delete")).clicked() {
+                self.presets.remove(self.selected_preset);
+                self.selected_preset = 0;
             }
         });
 
-        if !self.devices.is_empty() {
-            ui.separator();
-            ui.heading("Connected Devices:");
-            for device in &self.devices {
-                ui.label(format!("• {} ({})", device.id, device.status));
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.new_preset_name).hint_text("New preset name"));
+            let name = self.new_preset_name.trim().to_string();
+            if ui.add_enabled(!name.is_empty(), egui::Button::new("Save editor as preset")).clicked() {
+                match self.presets.iter().position(|p| p.name == name) {
+                    Some(index) => {
+                        self.presets[index].rules = self.rules.clone();
+                        self.selected_preset = index;
+                    }
+                    None => {
+                        self.presets.push(PortPreset {
+                            name,
+                            rules: self.rules.clone(),
+                            auto_apply: false,
+                        });
+                        self.selected_preset = self.presets.len() - 1;
+                    }
+                }
+                self.new_preset_name.clear();
             }
+        });
+    }
+
+    fn show_reapply(&mut self, ui: &mut egui::Ui, context: &DeviceContext) {
+        ui.checkbox(&mut self.reapply_on_reconnect, "Re-apply rules when a device reconnects")
+            .on_hover_text("Mappings are lost on reboot or replug; restore the ones applied from this tab");
+
+        let mut forgotten = None;
+        let mut keys: Vec<&String> = self.desired_rules.keys().collect();
+        keys.sort();
+        for key in keys {
+            // Show the nickname when the device is currently attached
+            let name = match self.device_keys.iter().find(|(_, k)| *k == key) {
+                Some((device_id, _)) => context.labels.display_name(device_id),
+                None => key.clone(),
+            };
+            ui.horizontal(|ui| {
+                ui.label(format!("{}: {} rule(s)", name, self.desired_rules[key].len()))
+                    .on_hover_text(self.desired_rules[key].iter().map(|r| r.to_string()).collect::<Vec<_>>().join("\n"));
+                if ui.small_button("Forget").clicked() {
+                    forgotten = Some(key.clone());
+                }
+            });
+        }
+        if let Some(key) = forgotten {
+            self.desired_rules.remove(&key);
         }
 
-        if !self.status_messages.is_empty() {
+        if !self.reapply_log.is_empty() {
             ui.separator();
-            ui.heading("Status:");
-            for message in &self.status_messages {
-                ui.label(message);
+            for entry in self.reapply_log.iter().rev() {
+                ui.label(entry);
             }
+            if ui.button("Clear log").clicked() {
+                self.reapply_log.clear();
+            }
+        }
+    }
+
+    fn show_rule_editor(&mut self, ui: &mut egui::Ui) {
+        let listening: Vec<Option<bool>> = self
+            .rules
+            .clone()
+            .iter()
+            .map(|r| match r.host.port() {
+                Some(port) if r.direction == Direction::Reverse && port != 0 => Some(self.probe_listener(port)),
+                _ => None,
+            })
+            .collect();
+        if listening.iter().any(Option::is_some) {
+            ui.ctx().request_repaint_after(LISTENER_PROBE_INTERVAL);
         }
 
+        let mut removed = None;
+        egui::Grid::new("port_rules").num_columns(7).striped(true).show(ui, |ui| {
+            ui.strong("Type");
+            ui.strong("Device socket");
+            ui.strong("Host socket");
+            ui.strong("No rebind");
+            ui.strong("Host listener");
+            ui.end_row();
+
+            for (index, rule) in self.rules.iter_mut().enumerate() {
+                egui::ComboBox::from_id_source(("rule_direction", index))
+                    .width(80.0)
+                    .selected_text(rule.direction.label())
+                    .show_ui(ui, |ui| {
+                        for direction in [Direction::Reverse, Direction::Forward] {
+                            ui.selectable_value(&mut rule.direction, direction, direction.label());
+                        }
+                    });
+                ui.horizontal(|ui| socket_spec_editor(ui, ("reverse_device", index), &mut rule.device));
+                ui.horizontal(|ui| socket_spec_editor(ui, ("reverse_host", index), &mut rule.host))
+                    .response
+                    .on_hover_text("For a forward, tcp:0 lets adb pick a free host port");
+                let bound_socket = match rule.direction {
+                    Direction::Reverse => "device socket",
+                    Direction::Forward => "host socket",
+                };
+                ui.checkbox(&mut rule.no_rebind, "")
+                    .on_hover_text(format!("Fail instead of replacing an existing rule for the same {}", bound_socket));
+                match listening[index] {
+                    Some(true) => ui.colored_label(egui::Color32::GREEN, "listening"),
+                    Some(false) => ui.colored_label(egui::Color32::RED, "not listening")
+                        .on_hover_text("Nothing on this computer accepts connections on this port; start the dev server"),
+                    None => ui.weak("-"),
+                };
+                if ui.small_button("✖").on_hover_text("Remove from the list").clicked() {
+                    removed = Some(index);
+                }
+                if let Err(e) = rule.validate() {
+                    ui.colored_label(egui::Color32::RED, "⚠").on_hover_text(e);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(index) = removed {
+            self.rules.remove(index);
+        }
+        ui.horizontal(|ui| {
+            let next_port = self
+                .rules
+                .iter()
+                .filter_map(|r| r.host.port())
+                .max()
+                .map_or(8081, |p| p.saturating_add(1));
+            if ui.button("Add reverse rule").clicked() {
+                self.rules.push(PortRule::tcp(Direction::Reverse, next_port, next_port));
+            }
+            if ui.button("Add forward rule").clicked() {
+                self.rules.push(PortRule::tcp(Direction::Forward, next_port, next_port));
+            }
+            if ui.button("Add Chrome DevTools forward").clicked() {
+                let mut rule = PortRule::tcp(Direction::Forward, 0, 9222);
+                rule.device = SocketSpec {
+                    kind: SocketKind::LocalAbstract,
+                    value: "chrome_devtools_remote".to_string(),
+                };
+                self.rules.push(rule);
+            }
+        });
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, context: &mut DeviceContext) -> Option<String> {
+        ui.heading("Reverse & Forward Setup");
+        self.sync_devices(context);
+        self.receive_events();
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.collapsing("Presets", |ui| self.show_presets(ui, context));
+            ui.separator();
+            self.show_rule_editor(ui);
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("Refresh Devices").clicked() {
+                    context.refresh();
+                }
+                let invalid = self.rules.iter().find_map(|r| r.validate().err());
+                let apply_enabled = invalid.is_none() && !self.rules.is_empty();
+                if ui.add_enabled(apply_enabled, egui::Button::new("Apply to Selected"))
+                    .on_hover_text("The multi-selected devices, or the active device")
+                    .on_disabled_hover_text(invalid.clone().unwrap_or_default())
+                    .clicked()
+                {
+                    let ctx = ui.ctx().clone();
+                    self.for_each_usable(context.target_devices(), |tab, device| tab.apply_rules(device, &ctx));
+                }
+                if ui.add_enabled(apply_enabled, egui::Button::new("Apply to All"))
+                    .on_disabled_hover_text(invalid.unwrap_or_default())
+                    .clicked()
+                {
+                    let ctx = ui.ctx().clone();
+                    self.for_each_usable(self.devices.clone(), |tab, device| tab.apply_rules(device, &ctx));
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Remove listed rules from Selected").clicked() {
+                    self.for_each_usable(context.target_devices(), Self::remove_rules);
+                }
+                if ui.button("Remove all rules from Selected").on_hover_text("adb reverse --remove-all and adb forward --remove-all").clicked() {
+                    self.for_each_usable(context.target_devices(), Self::remove_all_rules);
+                }
+            });
+
+            if !self.devices.is_empty() {
+                ui.separator();
+                ui.heading("Connected Devices:");
+                let mut recover = None;
+                for device in &self.devices {
+                    ui.horizontal(|ui| {
+                        let mut selected = context.selection.is_in_set(&device.id);
+                        let checkbox = egui::Checkbox::new(&mut selected, context.device_label(device));
+                        if ui.add_enabled(device.is_usable(), checkbox)
+                            .on_disabled_hover_text(device.state_hint().unwrap_or_default())
+                            .changed()
+                        {
+                            context.toggle_in_set(&device.id);
+                        }
+                        if device.state == DeviceState::Offline && ui.small_button("Reconnect & re-apply").clicked() {
+                            recover = Some(device.clone());
+                        }
+                    });
+                }
+                if let Some(device) = recover {
+                    self.recover_device(&device, ui.ctx());
+                }
+            }
+
+            ui.separator();
+            ui.collapsing("Active mappings", |ui| self.show_active_mappings(ui, context));
+            ui.collapsing("Re-apply after reconnect", |ui| self.show_reapply(ui, context));
+            ui.collapsing("Traffic monitor", |ui| self.show_traffic_monitor(ui, context));
+
+            if !self.outcomes.is_empty() || !self.status_messages.is_empty() {
+                ui.separator();
+                ui.heading("Status:");
+                if !self.outcomes.is_empty() {
+                    self.show_outcomes(ui, context);
+                }
+                for message in &self.status_messages {
+                    ui.label(message);
+                }
+            }
+        });
+
         None
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restoring_a_monitored_rule_allows_rebinding() {
+        let mut rule = PortRule::tcp(Direction::Reverse, 8081, 8081);
+        rule.no_rebind = true;
+        let restored = restore_rule(&rule);
+        assert!(!restored.no_rebind);
+        assert_eq!(restored.args(), ["reverse", "tcp:8081", "tcp:8081"]);
+        assert_eq!(restored.device, rule.device);
+        assert_eq!(restored.host, rule.host);
+    }
+}
@@ -0,0 +1,541 @@
+use eframe::egui;
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use crate::adb::{AdbManager, ShellOutput};
+use crate::device_context::DeviceContext;
+use crate::shell_session::ShellSession;
+
+// Oldest output is dropped beyond this many bytes
+const MAX_OUTPUT: usize = 256 * 1024;
+const MAX_HISTORY: usize = 200;
+const BROADCAST_TIMEOUT: Duration = Duration::from_secs(60);
+// Line diffs are quadratic, so very long outputs are only compared as a whole
+const MAX_DIFF_CELLS: usize = 1_000_000;
+
+#[derive(Clone, Copy, PartialEq)]
+enum ShellMode {
+    Console,
+    Broadcast,
+}
+
+// One command run on several devices at once
+struct BroadcastRun {
+    command: String,
+    devices: Vec<String>,
+    results: BTreeMap<String, Result<ShellOutput, String>>,
+    receiver: Receiver<(String, Result<ShellOutput, String>)>,
+}
+
+impl BroadcastRun {
+    fn is_done(&self) -> bool {
+        self.results.len() == self.devices.len()
+    }
+
+    // Output as shown side by side and compared in the diff
+    fn text(&self, device_id: &str) -> String {
+        match self.results.get(device_id) {
+            Some(Ok(output)) if output.stderr.trim().is_empty() => output.stdout.clone(),
+            Some(Ok(output)) => format!("{}\n[stderr]\n{}", output.stdout, output.stderr),
+            Some(Err(e)) => format!("[error] {}", e.trim()),
+            None => String::new(),
+        }
+    }
+
+    fn distinct_outputs(&self) -> usize {
+        let mut outputs: Vec<String> = self.devices.iter().map(|d| self.text(d)).collect();
+        outputs.sort();
+        outputs.dedup();
+        outputs.len()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DiffKind {
+    Same,
+    Removed,
+    Added,
+}
+
+type Diff = Vec<(DiffKind, String)>;
+
+// Longest-common-subsequence line diff of `a` against `b`, or None when the outputs are too long
+fn diff_lines(a: &str, b: &str) -> Option<Diff> {
+    let a: Vec<&str> = a.lines().collect();
+    let b: Vec<&str> = b.lines().collect();
+    if a.len().saturating_mul(b.len()) > MAX_DIFF_CELLS {
+        return None;
+    }
+
+    let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            lines.push((DiffKind::Same, a[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if j < b.len() && (i == a.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            lines.push((DiffKind::Added, b[j].to_string()));
+            j += 1;
+        } else {
+            lines.push((DiffKind::Removed, a[i].to_string()));
+            i += 1;
+        }
+    }
+    Some(lines)
+}
+
+pub struct ShellTab {
+    adb_manager: Arc<AdbManager>,
+    session: Option<ShellSession>,
+    // Device the session runs on
+    device_id: String,
+    output: String,
+    input: String,
+    history: Vec<String>,
+    // Position while browsing the history with the arrow keys
+    history_index: Option<usize>,
+    status: String,
+    mode: ShellMode,
+    broadcast_command: String,
+    broadcast: Option<BroadcastRun>,
+    // Devices compared in the diff view, as indices into the run's devices
+    diff_pair: (usize, usize),
+    diff_cache: Option<((usize, usize), Option<Diff>)>,
+}
+
+impl Default for ShellTab {
+    fn default() -> Self {
+        Self::new(Arc::new(AdbManager::new()))
+    }
+}
+
+impl ShellTab {
+    pub fn new(adb_manager: Arc<AdbManager>) -> Self {
+        Self {
+            adb_manager,
+            session: None,
+            device_id: String::new(),
+            output: String::new(),
+            input: String::new(),
+            history: Vec::new(),
+            history_index: None,
+            status: String::new(),
+            mode: ShellMode::Console,
+            broadcast_command: String::new(),
+            broadcast: None,
+            diff_pair: (0, 1),
+            diff_cache: None,
+        }
+    }
+
+    fn connect(&mut self, device_id: &str, ctx: &egui::Context) {
+        self.session = None;
+        match ShellSession::start(&self.adb_manager, device_id, ctx.clone()) {
+            Ok(session) => {
+                self.session = Some(session);
+                self.device_id = device_id.to_string();
+                self.status = format!("Connected to {}", device_id);
+            }
+            Err(e) => self.status = format!("Failed to start shell: {}", e),
+        }
+    }
+
+    // Called every frame, whichever tab is showing, so output never backs up in the channel
+    pub fn update(&mut self) {
+        let Some(session) = &mut self.session else {
+            return;
+        };
+        self.output.push_str(&session.receive());
+        if let Some(message) = session.exit_message() {
+            self.output.push_str(&session.receive());
+            self.status = message;
+            self.session = None;
+        }
+
+        if self.output.len() > MAX_OUTPUT {
+            // Cut at a line boundary so the first visible line is whole
+            let cut = self.output.len() - MAX_OUTPUT;
+            let cut = (cut..self.output.len()).find(|&i| self.output.is_char_boundary(i)).unwrap_or(self.output.len());
+            let cut = self.output[cut..].find('\n').map_or(cut, |i| cut + i + 1);
+            self.output.drain(..cut);
+        }
+    }
+
+    fn send(&mut self, text: &str) {
+        if let Some(session) = &mut self.session {
+            if let Err(e) = session.send(text) {
+                self.status = format!("Failed to write to the shell: {}", e);
+            }
+        }
+    }
+
+    fn submit(&mut self) {
+        let command = std::mem::take(&mut self.input);
+        self.send(&format!("{}\n", command));
+        if !command.trim().is_empty() && self.history.last() != Some(&command) {
+            self.history.push(command);
+            if self.history.len() > MAX_HISTORY {
+                self.history.remove(0);
+            }
+        }
+        self.history_index = None;
+    }
+
+    fn interrupt(&mut self) {
+        if let Some(session) = &mut self.session {
+            if let Err(e) = session.interrupt() {
+                self.status = format!("Failed to send Ctrl-C: {}", e);
+            }
+        }
+    }
+
+    fn browse_history(&mut self, older: bool) {
+        if self.history.is_empty() {
+            return;
+        }
+        self.history_index = match (self.history_index, older) {
+            (None, true) => Some(self.history.len() - 1),
+            (None, false) => None,
+            (Some(i), true) => Some(i.saturating_sub(1)),
+            (Some(i), false) if i + 1 < self.history.len() => Some(i + 1),
+            (Some(_), false) => None,
+        };
+        self.input = self.history_index.map(|i| self.history[i].clone()).unwrap_or_default();
+    }
+
+    fn show_input(&mut self, ui: &mut egui::Ui) {
+        let connected = self.session.is_some();
+        ui.horizontal(|ui| {
+            ui.monospace("$");
+            let response = ui.add_enabled(
+                connected,
+                egui::TextEdit::singleline(&mut self.input)
+                    .font(egui::TextStyle::Monospace)
+                    .desired_width(ui.available_width() - 160.0)
+                    .hint_text("Command, Enter to run, ↑/↓ for history"),
+            );
+
+            if response.has_focus() {
+                let (up, down, copy) = ui.input(|i| {
+                    (
+                        i.key_pressed(egui::Key::ArrowUp),
+                        i.key_pressed(egui::Key::ArrowDown),
+                        i.events.contains(&egui::Event::Copy),
+                    )
+                });
+                if up || down {
+                    self.browse_history(up);
+                }
+                // Ctrl-C with nothing typed goes to the device, like in a terminal
+                if copy && self.input.is_empty() {
+                    self.interrupt();
+                }
+            }
+            if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                self.submit();
+                response.request_focus();
+            }
+
+            if ui.add_enabled(connected, egui::Button::new("Run")).clicked() {
+                self.submit();
+            }
+            if ui.add_enabled(connected, egui::Button::new("Ctrl-C")).on_hover_text("Stop the running command").clicked() {
+                self.interrupt();
+            }
+        });
+    }
+
+    fn start_broadcast(&mut self, context: &DeviceContext, ctx: &egui::Context) {
+        let command = self.broadcast_command.trim().to_string();
+        let devices: Vec<String> = context
+            .target_devices()
+            .into_iter()
+            .filter(|d| d.is_usable())
+            .map(|d| d.id)
+            .collect();
+
+        let (sender, receiver) = mpsc::channel();
+        for device_id in &devices {
+            let adb_manager = self.adb_manager.clone();
+            let device_id = device_id.clone();
+            let command = command.clone();
+            let sender = sender.clone();
+            let ctx = ctx.clone();
+            thread::spawn(move || {
+                let result = adb_manager.run_shell(&device_id, &command, BROADCAST_TIMEOUT);
+                let _ = sender.send((device_id, result));
+                ctx.request_repaint();
+            });
+        }
+
+        self.broadcast = Some(BroadcastRun {
+            command,
+            devices,
+            results: BTreeMap::new(),
+            receiver,
+        });
+        self.diff_pair = (0, 1);
+        self.diff_cache = None;
+    }
+
+    fn show_broadcast(&mut self, ui: &mut egui::Ui, context: &DeviceContext) -> Option<String> {
+        let targets: Vec<String> = context
+            .target_devices()
+            .iter()
+            .filter(|d| d.is_usable())
+            .map(|d| context.labels.display_name(&d.id))
+            .collect();
+        ui.label(format!("Runs on the multi-selected devices, or the active device: {}", if targets.is_empty() { "none".to_string() } else { targets.join(", ") }));
+
+        let running = self.broadcast.as_ref().is_some_and(|b| !b.is_done());
+        ui.horizontal(|ui| {
+            ui.monospace("$");
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.broadcast_command)
+                    .font(egui::TextStyle::Monospace)
+                    .desired_width(ui.available_width() - 80.0)
+                    .hint_text("getprop ro.build.version.release"),
+            );
+            let enter = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            let can_run = !running && !targets.is_empty() && !self.broadcast_command.trim().is_empty();
+            if (ui.add_enabled(can_run, egui::Button::new("Run")).clicked() || enter) && can_run {
+                self.start_broadcast(context, ui.ctx());
+            }
+        });
+
+        let Some(run) = &mut self.broadcast else {
+            return None;
+        };
+        for (device_id, result) in run.receiver.try_iter() {
+            run.results.insert(device_id, result);
+        }
+
+        ui.separator();
+        if run.is_done() {
+            let distinct = run.distinct_outputs();
+            let summary = if distinct <= 1 {
+                egui::RichText::new(format!("`{}` on {} device(s): identical output", run.command, run.devices.len()))
+            } else {
+                egui::RichText::new(format!("`{}` on {} device(s): {} different outputs", run.command, run.devices.len(), distinct))
+                    .color(egui::Color32::YELLOW)
+            };
+            ui.label(summary);
+        } else {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(format!("`{}`: {} of {} device(s) done", run.command, run.results.len(), run.devices.len()));
+            });
+        }
+
+        let mut message = None;
+        egui::ScrollArea::horizontal().id_source("broadcast_columns").show(ui, |ui| {
+            ui.horizontal_top(|ui| {
+                for device_id in &run.devices {
+                    ui.vertical(|ui| {
+                        ui.set_width(320.0);
+                        ui.label(context.labels.rich_text(device_id, context.labels.display_name(device_id)).strong());
+                        ui.horizontal(|ui| {
+                            match run.results.get(device_id) {
+                                Some(Ok(output)) => match output.exit_code {
+                                    Some(0) => ui.colored_label(egui::Color32::GREEN, "exit 0"),
+                                    Some(code) => ui.colored_label(egui::Color32::RED, format!("exit {}", code)),
+                                    None => ui.colored_label(egui::Color32::RED, "killed"),
+                                },
+                                Some(Err(e)) => ui.colored_label(egui::Color32::RED, "failed").on_hover_text(e.trim()),
+                                None => ui.spinner(),
+                            };
+                            if run.results.contains_key(device_id) && ui.small_button("Copy").clicked() {
+                                ui.output_mut(|o| o.copied_text = run.text(device_id));
+                                message = Some(format!("Output of {} copied to the clipboard", device_id));
+                            }
+                        });
+                        let text = run.text(device_id);
+                        egui::ScrollArea::vertical().id_source(("broadcast_output", device_id)).max_height(280.0).show(ui, |ui| {
+                            ui.add(
+                                egui::TextEdit::multiline(&mut text.as_str())
+                                    .font(egui::TextStyle::Monospace)
+                                    .desired_width(310.0),
+                            );
+                        });
+                    });
+                }
+            });
+        });
+
+        if run.is_done() && run.distinct_outputs() > 1 {
+            ui.separator();
+            ui.collapsing("Differences", |ui| self.show_diff(ui, context));
+        }
+        message
+    }
+
+    fn show_diff(&mut self, ui: &mut egui::Ui, context: &DeviceContext) {
+        let Some(run) = &self.broadcast else {
+            return;
+        };
+        let (mut base, mut other) = self.diff_pair;
+        ui.horizontal(|ui| {
+            for (label, index) in [("Compare", &mut base), ("with", &mut other)] {
+                ui.label(label);
+                egui::ComboBox::from_id_source(("diff_device", label))
+                    .selected_text(context.labels.display_name(&run.devices[(*index).min(run.devices.len() - 1)]))
+                    .show_ui(ui, |ui| {
+                        for (i, device_id) in run.devices.iter().enumerate() {
+                            ui.selectable_value(index, i, context.labels.display_name(device_id));
+                        }
+                    });
+            }
+        });
+        let pair = (base.min(run.devices.len() - 1), other.min(run.devices.len() - 1));
+        self.diff_pair = pair;
+
+        if self.diff_cache.as_ref().is_none_or(|(cached, _)| *cached != pair) {
+            let lines = diff_lines(&run.text(&run.devices[pair.0]), &run.text(&run.devices[pair.1]));
+            self.diff_cache = Some((pair, lines));
+        }
+        let Some((_, lines)) = &self.diff_cache else {
+            return;
+        };
+        let Some(lines) = lines else {
+            ui.label("The outputs are too long to compare line by line");
+            return;
+        };
+
+        egui::ScrollArea::both().id_source("broadcast_diff").max_height(300.0).show(ui, |ui| {
+            for (kind, line) in lines {
+                let (prefix, color) = match kind {
+                    DiffKind::Same => (" ", ui.visuals().text_color()),
+                    DiffKind::Removed => ("-", egui::Color32::from_rgb(240, 80, 80)),
+                    DiffKind::Added => ("+", egui::Color32::from_rgb(100, 200, 100)),
+                };
+                let text = egui::RichText::new(format!("{} {}", prefix, line)).monospace().color(color);
+                ui.add(egui::Label::new(text).wrap(false));
+            }
+        });
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, context: &mut DeviceContext) -> Option<String> {
+        ui.heading("Shell");
+
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.mode, ShellMode::Console, "Console");
+            ui.selectable_value(&mut self.mode, ShellMode::Broadcast, "Run on several devices");
+        });
+        ui.separator();
+        if self.mode == ShellMode::Broadcast {
+            return self.show_broadcast(ui, context);
+        }
+
+        let mut message = None;
+        ui.horizontal(|ui| {
+            context.device_picker(ui, "shell_device");
+            let active = context.active_device().filter(|d| d.is_usable()).map(|d| d.id.clone());
+            let label = if self.session.is_some() { "Reconnect" } else { "Connect" };
+            if ui.add_enabled(active.is_some(), egui::Button::new(label)).clicked() {
+                if let Some(device_id) = &active {
+                    self.connect(device_id, ui.ctx());
+                }
+            }
+            if self.session.is_some() && ui.button("Disconnect").clicked() {
+                self.session = None;
+                self.status = "Disconnected".to_string();
+            }
+            if ui.button("Copy all").clicked() {
+                ui.output_mut(|o| o.copied_text = self.output.clone());
+                message = Some("Shell output copied to the clipboard".to_string());
+            }
+            if ui.button("Clear").clicked() {
+                self.output.clear();
+            }
+            ui.weak(&self.status);
+            if self.session.is_some() && active.as_deref().is_some_and(|a| a != self.device_id) {
+                ui.colored_label(egui::Color32::YELLOW, format!("Session is on {}", context.labels.display_name(&self.device_id)));
+            }
+        });
+
+        self.show_input(ui);
+        ui.separator();
+
+        egui::ScrollArea::both()
+            .auto_shrink([false, false])
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                // Read-only but selectable, so parts of the output can be copied
+                let mut output = self.output.as_str();
+                ui.add(
+                    egui::TextEdit::multiline(&mut output)
+                        .font(egui::TextStyle::Monospace)
+                        .desired_width(f32::INFINITY),
+                );
+            });
+
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(a: &str, b: &str) -> Vec<(DiffKind, String)> {
+        diff_lines(a, b).unwrap()
+    }
+
+    fn expected(lines: &[(DiffKind, &str)]) -> Diff {
+        lines.iter().map(|(kind, line)| (*kind, line.to_string())).collect()
+    }
+
+    #[test]
+    fn identical_outputs_are_all_same() {
+        assert_eq!(diff("a\nb", "a\nb"), expected(&[(DiffKind::Same, "a"), (DiffKind::Same, "b")]));
+        assert!(diff_lines("", "").unwrap().is_empty());
+    }
+
+    #[test]
+    fn added_lines() {
+        assert_eq!(
+            diff("a\nc", "a\nb\nc\nd"),
+            expected(&[(DiffKind::Same, "a"), (DiffKind::Added, "b"), (DiffKind::Same, "c"), (DiffKind::Added, "d")])
+        );
+    }
+
+    #[test]
+    fn removed_lines() {
+        assert_eq!(
+            diff("a\nb\nc", "b"),
+            expected(&[(DiffKind::Removed, "a"), (DiffKind::Same, "b"), (DiffKind::Removed, "c")])
+        );
+    }
+
+    #[test]
+    fn changed_line_is_removed_and_added() {
+        let lines = diff("sdk=33\nabi=arm64", "sdk=34\nabi=arm64");
+        assert_eq!(lines.len(), 3);
+        assert!(lines.contains(&(DiffKind::Removed, "sdk=33".to_string())));
+        assert!(lines.contains(&(DiffKind::Added, "sdk=34".to_string())));
+        assert_eq!(lines[2], (DiffKind::Same, "abi=arm64".to_string()));
+    }
+
+    #[test]
+    fn long_outputs_are_not_diffed() {
+        let a = "x\n".repeat(1001);
+        let b = "y\n".repeat(1000);
+        assert!(a.lines().count() * b.lines().count() > MAX_DIFF_CELLS);
+        assert!(diff_lines(&a, &b).is_none());
+        assert!(diff_lines(&a, "y").is_some());
+    }
+}
@@ -0,0 +1,138 @@
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Default)]
+struct TrafficCounters {
+    connections: AtomicU64,
+    active: AtomicU64,
+    // Connections the proxy accepted but could not pass on because nothing listened on the target
+    refused: AtomicU64,
+    bytes_to_host: AtomicU64,
+    bytes_to_device: AtomicU64,
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct TrafficStats {
+    pub connections: u64,
+    pub active: u64,
+    pub refused: u64,
+    pub bytes_to_host: u64,
+    pub bytes_to_device: u64,
+}
+
+// Pass-through TCP proxy on a free local port that forwards to `target_port` and counts traffic.
+// Pointing a device's reverse rule at it shows whether that device's requests arrive at all.
+pub struct TrafficProxy {
+    listen_port: u16,
+    target_port: u16,
+    counters: Arc<TrafficCounters>,
+    stop: Arc<AtomicBool>,
+}
+
+impl TrafficProxy {
+    pub fn start(target_port: u16) -> Result<Self, String> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).map_err(|e| e.to_string())?;
+        let listen_port = listener.local_addr().map_err(|e| e.to_string())?.port();
+        let counters = Arc::new(TrafficCounters::default());
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread_counters = counters.clone();
+        let thread_stop = stop.clone();
+        thread::spawn(move || {
+            for client in listener.incoming() {
+                if thread_stop.load(Ordering::Relaxed) {
+                    break;
+                }
+                if let Ok(client) = client {
+                    let counters = thread_counters.clone();
+                    thread::spawn(move || handle_connection(client, target_port, counters));
+                }
+            }
+        });
+
+        Ok(Self {
+            listen_port,
+            target_port,
+            counters,
+            stop,
+        })
+    }
+
+    pub fn listen_port(&self) -> u16 {
+        self.listen_port
+    }
+
+    pub fn target_port(&self) -> u16 {
+        self.target_port
+    }
+
+    pub fn stats(&self) -> TrafficStats {
+        TrafficStats {
+            connections: self.counters.connections.load(Ordering::Relaxed),
+            active: self.counters.active.load(Ordering::Relaxed),
+            refused: self.counters.refused.load(Ordering::Relaxed),
+            bytes_to_host: self.counters.bytes_to_host.load(Ordering::Relaxed),
+            bytes_to_device: self.counters.bytes_to_device.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Drop for TrafficProxy {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // Wake the accept loop so it sees the stop flag
+        let _ = TcpStream::connect((Ipv4Addr::LOCALHOST, self.listen_port));
+    }
+}
+
+fn handle_connection(client: TcpStream, target_port: u16, counters: Arc<TrafficCounters>) {
+    counters.connections.fetch_add(1, Ordering::Relaxed);
+    let target = SocketAddr::from((Ipv4Addr::LOCALHOST, target_port));
+    let server = match TcpStream::connect_timeout(&target, CONNECT_TIMEOUT) {
+        Ok(server) => server,
+        Err(_) => {
+            counters.refused.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+    };
+
+    let (Ok(client_reader), Ok(server_reader)) = (client.try_clone(), server.try_clone()) else {
+        return;
+    };
+    counters.active.fetch_add(1, Ordering::Relaxed);
+    let upload_counters = counters.clone();
+    let upload = thread::spawn(move || pipe(client_reader, server, &upload_counters.bytes_to_host));
+    pipe(server_reader, client, &counters.bytes_to_device);
+    let _ = upload.join();
+    counters.active.fetch_sub(1, Ordering::Relaxed);
+}
+
+fn pipe(mut from: TcpStream, mut to: TcpStream, counter: &AtomicU64) {
+    let mut buffer = [0u8; 16 * 1024];
+    loop {
+        match from.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                if to.write_all(&buffer[..n]).is_err() {
+                    break;
+                }
+                counter.fetch_add(n as u64, Ordering::Relaxed);
+            }
+        }
+    }
+    let _ = to.shutdown(Shutdown::Write);
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1 << 20 => format!("{:.1} MiB", b as f64 / (1 << 20) as f64),
+        b if b >= 1 << 10 => format!("{:.1} KiB", b as f64 / (1 << 10) as f64),
+        b => format!("{} B", b),
+    }
+}
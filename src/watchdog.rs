@@ -0,0 +1,250 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use crate::adb::AdbManager;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+const INITIAL_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+const MAX_LOG_ENTRIES: usize = 500;
+
+#[derive(Clone)]
+pub struct WatchdogEvent {
+    pub time: String,
+    pub target: String,
+    pub message: String,
+}
+
+struct Backoff {
+    delay: Duration,
+    next_attempt: Instant,
+    attempts: u32,
+}
+
+impl Backoff {
+    fn new() -> Self {
+        Self {
+            delay: INITIAL_BACKOFF,
+            next_attempt: Instant::now(),
+            attempts: 0,
+        }
+    }
+
+    // Schedules the next attempt after a failed one and returns how long until then
+    fn failed(&mut self) -> Duration {
+        self.attempts += 1;
+        self.next_attempt = Instant::now() + self.delay;
+        let retry_in = self.delay;
+        self.delay = (self.delay * 2).min(MAX_BACKOFF);
+        retry_in
+    }
+}
+
+#[derive(Default)]
+struct WatchdogState {
+    enabled: bool,
+    targets: Vec<String>,
+    backoff: HashMap<String, Backoff>,
+    log: VecDeque<WatchdogEvent>,
+}
+
+impl WatchdogState {
+    fn log(&mut self, target: &str, message: String) {
+        if self.log.len() >= MAX_LOG_ENTRIES {
+            self.log.pop_front();
+        }
+        self.log.push_back(WatchdogEvent {
+            time: utc_timestamp(),
+            target: target.to_string(),
+            message,
+        });
+    }
+}
+
+// Keeps saved wireless targets connected by retrying `adb connect` with exponential backoff.
+// The polling thread only runs while the watchdog is enabled.
+pub struct Watchdog {
+    adb_manager: Arc<AdbManager>,
+    state: Arc<Mutex<WatchdogState>>,
+    stop: Option<Arc<AtomicBool>>,
+}
+
+impl Watchdog {
+    pub fn new(adb_manager: Arc<AdbManager>) -> Self {
+        Self {
+            adb_manager,
+            state: Arc::new(Mutex::new(WatchdogState::default())),
+            stop: None,
+        }
+    }
+
+    fn spawn(&self) -> Arc<AtomicBool> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let thread_state = self.state.clone();
+        let adb_manager = self.adb_manager.clone();
+        thread::spawn(move || {
+            let mut last_check: Option<Instant> = None;
+            while !thread_stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_secs(1));
+
+                let targets = thread_state.lock().unwrap().targets.clone();
+                if thread_stop.load(Ordering::Relaxed) {
+                    break;
+                }
+                if targets.is_empty() {
+                    last_check = None;
+                    continue;
+                }
+                if last_check.is_some_and(|t| t.elapsed() < CHECK_INTERVAL) {
+                    continue;
+                }
+                last_check = Some(Instant::now());
+
+                let devices = match adb_manager.list_devices() {
+                    Ok(devices) => devices,
+                    Err(e) => {
+                        thread_state.lock().unwrap().log("adb", format!("Failed to list devices: {}", e.trim()));
+                        continue;
+                    }
+                };
+
+                for target in targets {
+                    let device = devices.iter().find(|d| d.id == target);
+                    if device.is_some_and(|d| d.is_usable()) {
+                        let mut state = thread_state.lock().unwrap();
+                        if let Some(backoff) = state.backoff.remove(&target) {
+                            state.log(&target, format!("Back online after {} attempt(s)", backoff.attempts));
+                        }
+                        continue;
+                    }
+
+                    let due = {
+                        let mut state = thread_state.lock().unwrap();
+                        if !state.backoff.contains_key(&target) {
+                            let reason = device.map_or("missing".to_string(), |d| d.state.to_string());
+                            state.log(&target, format!("Device is {}, reconnecting", reason));
+                            state.backoff.insert(target.clone(), Backoff::new());
+                        }
+                        state.backoff[&target].next_attempt <= Instant::now()
+                    };
+                    if !due {
+                        continue;
+                    }
+
+                    // A stale offline entry makes adb answer "already connected", so drop it first
+                    if device.is_some() {
+                        let _ = adb_manager.run_adb_command(&["disconnect", &target]);
+                    }
+                    let result = adb_manager.connect(&target);
+
+                    let mut state = thread_state.lock().unwrap();
+                    if result.is_connected() {
+                        let attempts = state.backoff.remove(&target).map_or(1, |b| b.attempts + 1);
+                        state.log(&target, format!("Reconnected after {} attempt(s)", attempts));
+                    } else if let Some(backoff) = state.backoff.get_mut(&target) {
+                        let retry_in = backoff.failed();
+                        state.log(&target, format!("{} (retrying in {}s)", result, retry_in.as_secs()));
+                    }
+                }
+            }
+        });
+        stop
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.enabled == enabled {
+                return;
+            }
+            state.enabled = enabled;
+            state.backoff.clear();
+            let message = if enabled { "Watchdog started" } else { "Watchdog stopped" };
+            state.log("watchdog", message.to_string());
+        }
+        if enabled {
+            self.stop = Some(self.spawn());
+        } else if let Some(stop) = self.stop.take() {
+            stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn set_targets(&self, targets: &[String]) {
+        let mut state = self.state.lock().unwrap();
+        state.targets = targets.to_vec();
+        state.backoff.retain(|target, _| targets.contains(target));
+    }
+
+    pub fn history(&self) -> Vec<WatchdogEvent> {
+        self.state.lock().unwrap().log.iter().cloned().collect()
+    }
+
+    pub fn clear_history(&self) {
+        self.state.lock().unwrap().log.clear();
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        if let Some(stop) = &self.stop {
+            stop.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+pub fn utc_timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    format_utc(secs)
+}
+
+// "2024-03-09 14:05:07 UTC"; the date matters when reading back an overnight session
+fn format_utc(secs: u64) -> String {
+    // Days since 1970-01-01 to a civil date (Howard Hinnant's days_from_civil, inverted)
+    let days = secs / 86_400;
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        (secs / 3600) % 24,
+        (secs / 60) % 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let mut backoff = Backoff::new();
+        let delays: Vec<u64> = (0..9).map(|_| backoff.failed().as_secs()).collect();
+        assert_eq!(delays, [5, 10, 20, 40, 80, 160, 300, 300, 300]);
+        assert_eq!(backoff.attempts, 9);
+        assert!(backoff.next_attempt > Instant::now());
+    }
+
+    #[test]
+    fn timestamps_include_the_date() {
+        assert_eq!(format_utc(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_utc(951_782_400), "2000-02-29 00:00:00 UTC");
+        assert_eq!(format_utc(1_710_000_000), "2024-03-09 16:00:00 UTC");
+        assert_eq!(format_utc(1_735_689_599), "2024-12-31 23:59:59 UTC");
+    }
+}